@@ -0,0 +1,21 @@
+//! Helpers shared by the benchmarks.
+
+use std::time::{Duration, Instant};
+
+/// Run `f` repeatedly for about two seconds and print how long each run took
+/// and the throughput of processing `bytes` bytes per run.
+pub fn time(name: &str, bytes: usize, mut f: impl FnMut()) {
+    f();
+    let mut runs = 0;
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(2) {
+        f();
+        runs += 1;
+    }
+    let per_run = start.elapsed() / runs;
+    let throughput = bytes as f64 / per_run.as_secs_f64() / 1e6;
+    println!(
+        "{:<24} {:>10.2?} per run {:>10.1} MB/s",
+        name, per_run, throughput
+    );
+}
@@ -0,0 +1,53 @@
+//! Compares decoding values from a `BufReader` through the free `read_*`
+//! functions against `BufDecoder`. Run with `cargo bench --bench decoder`.
+
+mod common;
+
+use std::{
+    hint::black_box,
+    io::{self, BufReader, Read},
+};
+
+use common::time;
+use extended_io::{self as eio, decoder::BufDecoder};
+
+const LEN: usize = 1 << 22;
+
+/// A source that isn't a slice, so that reads go through `BufReader`'s buffer
+/// the way they would for a file or socket.
+struct Source<'a>(&'a [u8]);
+
+impl Read for Source<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+fn main() {
+    let data = (0..LEN * 4).map(|x| x as u8).collect::<Vec<_>>();
+
+    time("read_u32", LEN * 4, || {
+        let mut src = BufReader::new(Source(&data));
+        for _ in 0..LEN {
+            black_box(eio::read_u32(&mut src).unwrap());
+        }
+    });
+    time("BufDecoder::read_u32", LEN * 4, || {
+        let mut src = BufDecoder::new(BufReader::new(Source(&data)));
+        for _ in 0..LEN {
+            black_box(src.read_u32().unwrap());
+        }
+    });
+    time("read_u16_le", LEN * 4, || {
+        let mut src = BufReader::new(Source(&data));
+        for _ in 0..LEN * 2 {
+            black_box(eio::read_u16_le(&mut src).unwrap());
+        }
+    });
+    time("BufDecoder::read_u16_le", LEN * 4, || {
+        let mut src = BufDecoder::new(BufReader::new(Source(&data)));
+        for _ in 0..LEN * 2 {
+            black_box(src.read_u16_le().unwrap());
+        }
+    });
+}
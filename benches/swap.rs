@@ -0,0 +1,41 @@
+//! Compares decoding a large big-endian array with one `read_*` call per
+//! element against `read_slice`. Run with `cargo bench --bench swap`.
+
+mod common;
+
+use common::time;
+use extended_io::{self as eio, swap, ByteOrder};
+
+const LEN: usize = 1 << 24;
+
+fn main() {
+    let data = (0..LEN * 4).map(|x| x as u8).collect::<Vec<_>>();
+
+    let mut u16s = vec![0u16; LEN];
+    time("read_u16 per element", LEN * 2, || {
+        let mut src = &data[..];
+        for val in u16s.iter_mut() {
+            *val = eio::read_u16(&mut src).unwrap();
+        }
+    });
+    time("read_slice::<u16>", LEN * 2, || {
+        swap::read_slice(&mut &data[..], &mut u16s, ByteOrder::Big).unwrap();
+    });
+    time("swap_in_place::<u16>", LEN * 2, || {
+        swap::swap_in_place(&mut u16s)
+    });
+
+    let mut f32s = vec![0f32; LEN];
+    time("read_f32 per element", LEN * 4, || {
+        let mut src = &data[..];
+        for val in f32s.iter_mut() {
+            *val = eio::read_f32(&mut src).unwrap();
+        }
+    });
+    time("read_slice::<f32>", LEN * 4, || {
+        swap::read_slice(&mut &data[..], &mut f32s, ByteOrder::Big).unwrap();
+    });
+    time("swap_in_place::<f32>", LEN * 4, || {
+        swap::swap_in_place(&mut f32s)
+    });
+}
@@ -0,0 +1,657 @@
+//! Adapters which encode the bytes written to them as Base64, Base32, or
+//! Base16 (hex) text, and which decode such text back into bytes as it's read,
+//! so that neither side needs to hold the whole input in memory.
+//!
+//! Each base has a writer and a reader type: [`Base64Writer`] and
+//! [`Base64Reader`], [`Base32Writer`] and [`Base32Reader`], and
+//! [`Base16Writer`] and [`Base16Reader`]. Base64 also supports the URL-safe
+//! alphabet, and Base32 supports both the RFC 4648 "base32hex" alphabet and
+//! Crockford's alphabet. Readers skip ASCII whitespace, so wrapped lines can be
+//! read back without any extra configuration.
+//!
+//! [`Base64Writer`]: type.Base64Writer.html
+//! [`Base64Reader`]: type.Base64Reader.html
+//! [`Base32Writer`]: type.Base32Writer.html
+//! [`Base32Reader`]: type.Base32Reader.html
+//! [`Base16Writer`]: type.Base16Writer.html
+//! [`Base16Reader`]: type.Base16Reader.html
+
+use std::{
+    io::{self, Error, Read, Write},
+    marker::PhantomData,
+};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const BASE64_URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+const BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const BASE32_HEX: &[u8; 32] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
+const BASE32_CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const BASE16: &[u8; 16] = b"0123456789ABCDEF";
+const BASE16_LOWER: &[u8; 16] = b"0123456789abcdef";
+
+const PAD: u8 = b'=';
+const INVALID: u8 = 0xFF;
+
+/// Whether encoded text ends with `=` characters that pad it to a whole
+/// number of groups. Base16 never needs padding, so this has no effect on it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Padding {
+    /// Writers pad their output, and readers reject unpadded input.
+    Required,
+    /// Writers pad their output, and readers accept input with or without
+    /// padding.
+    Optional,
+    /// Writers don't pad their output, and readers reject padded input.
+    Forbidden,
+}
+
+/// The settings shared by the writers and readers of every base.
+#[derive(Clone, Copy, Debug)]
+struct Encoding {
+    alphabet: &'static [u8],
+    bits: u32,
+    padding: Padding,
+    case_insensitive: bool,
+    crockford: bool,
+}
+
+impl Encoding {
+    /// The number of characters in a group that encodes a whole number of
+    /// bytes.
+    fn group(&self) -> usize {
+        match self.bits {
+            4 => 2,
+            5 => 8,
+            _ => 4,
+        }
+    }
+
+    fn decode_table(&self) -> [u8; 256] {
+        let mut table = [INVALID; 256];
+        for (val, &c) in self.alphabet.iter().enumerate() {
+            table[c as usize] = val as u8;
+            if self.case_insensitive {
+                table[c.to_ascii_lowercase() as usize] = val as u8;
+            }
+        }
+        if self.crockford {
+            for (c, val) in [
+                (b'O', 0),
+                (b'o', 0),
+                (b'I', 1),
+                (b'i', 1),
+                (b'L', 1),
+                (b'l', 1),
+            ] {
+                table[c as usize] = val;
+            }
+        }
+        table
+    }
+}
+
+/// A base which text can be encoded in. This trait is sealed.
+pub trait Base: private::Sealed {
+    #[doc(hidden)]
+    const ALPHABET: &'static [u8];
+    #[doc(hidden)]
+    const BITS: u32;
+    #[doc(hidden)]
+    const CASE_INSENSITIVE: bool;
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Base64 as described by RFC 4648.
+#[derive(Debug)]
+pub enum Base64 {}
+
+/// Base32 as described by RFC 4648.
+#[derive(Debug)]
+pub enum Base32 {}
+
+/// Base16, better known as hex.
+#[derive(Debug)]
+pub enum Base16 {}
+
+impl private::Sealed for Base64 {}
+impl private::Sealed for Base32 {}
+impl private::Sealed for Base16 {}
+
+impl Base for Base64 {
+    const ALPHABET: &'static [u8] = BASE64;
+    const BITS: u32 = 6;
+    const CASE_INSENSITIVE: bool = false;
+}
+
+impl Base for Base32 {
+    const ALPHABET: &'static [u8] = BASE32;
+    const BITS: u32 = 5;
+    const CASE_INSENSITIVE: bool = true;
+}
+
+impl Base for Base16 {
+    const ALPHABET: &'static [u8] = BASE16;
+    const BITS: u32 = 4;
+    const CASE_INSENSITIVE: bool = true;
+}
+
+fn default_encoding<B: Base>() -> Encoding {
+    Encoding {
+        alphabet: B::ALPHABET,
+        bits: B::BITS,
+        padding: Padding::Required,
+        case_insensitive: B::CASE_INSENSITIVE,
+        crockford: false,
+    }
+}
+
+/**
+ * Encodes every byte written to it and writes the text to the wrapped bit
+ * sink. The final partial group can only be encoded once the input is
+ * complete, so it's written by [`finish`] or when the writer is dropped.
+ *
+ * [`finish`]: #method.finish
+ */
+pub struct EncoderWriter<W: Write, B> {
+    inner: Option<W>,
+    encoding: Encoding,
+    wrap: Option<usize>,
+    acc: u32,
+    nbits: u32,
+    chars: usize,
+    column: usize,
+    buf: Vec<u8>,
+    _base: PhantomData<B>,
+}
+
+/// Encodes bytes as Base64.
+pub type Base64Writer<W> = EncoderWriter<W, Base64>;
+/// Encodes bytes as Base32.
+pub type Base32Writer<W> = EncoderWriter<W, Base32>;
+/// Encodes bytes as Base16.
+pub type Base16Writer<W> = EncoderWriter<W, Base16>;
+
+impl<W: Write, B: Base> EncoderWriter<W, B> {
+    /// Create a writer which writes text in the standard alphabet with padding
+    /// and without line breaks to `inner`.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner: Some(inner),
+            encoding: default_encoding::<B>(),
+            wrap: None,
+            acc: 0,
+            nbits: 0,
+            chars: 0,
+            column: 0,
+            buf: Vec::new(),
+            _base: PhantomData,
+        }
+    }
+}
+
+impl<W: Write, B> EncoderWriter<W, B> {
+    /// Use the specified padding policy.
+    pub fn padding(mut self, padding: Padding) -> Self {
+        self.encoding.padding = padding;
+        self
+    }
+
+    /// Break the text into lines of `width` characters, such as 76 for MIME or
+    /// 64 for PEM. The last line doesn't end with a line break.
+    ///
+    /// # Panics
+    /// Panics if `width` is `Some(0)`.
+    pub fn wrap(mut self, width: Option<usize>) -> Self {
+        assert!(width != Some(0), "Lines must hold at least one character");
+        self.wrap = width;
+        self
+    }
+
+    /// Get a reference to the underlying bit sink.
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    fn push_char(&mut self, c: u8) {
+        if self.wrap == Some(self.column) {
+            self.buf.push(b'\n');
+            self.column = 0;
+        }
+        self.buf.push(c);
+        self.column += 1;
+        self.chars += 1;
+    }
+
+    fn write_end(&mut self) -> io::Result<()> {
+        let bits = self.encoding.bits;
+        if self.nbits > 0 {
+            let val = (self.acc << (bits - self.nbits)) & ((1 << bits) - 1);
+            self.push_char(self.encoding.alphabet[val as usize]);
+            self.nbits = 0;
+        }
+        if self.encoding.padding != Padding::Forbidden {
+            while !self.chars.is_multiple_of(self.encoding.group()) {
+                self.push_char(PAD);
+            }
+        }
+        self.write_buf()?;
+        self.inner.as_mut().unwrap().flush()
+    }
+
+    /// Write out the encoded text that hasn't been written yet, keeping
+    /// whatever the underlying bit sink didn't accept if it fails.
+    fn write_buf(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        while !self.buf.is_empty() {
+            match inner.write(&self.buf) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.buf.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the final partial group and any padding, then return the
+    /// underlying bit sink.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_end()?;
+        Ok(self.inner.take().unwrap())
+    }
+}
+
+impl<W: Write> EncoderWriter<W, Base64> {
+    /// Use the URL- and filename-safe alphabet, which has `-` and `_` in place
+    /// of `+` and `/`.
+    pub fn url_safe(mut self) -> Self {
+        self.encoding.alphabet = BASE64_URL;
+        self
+    }
+}
+
+impl<W: Write> EncoderWriter<W, Base32> {
+    /// Use the "base32hex" alphabet, which preserves the sort order of the
+    /// encoded data.
+    pub fn hex_alphabet(mut self) -> Self {
+        self.encoding.alphabet = BASE32_HEX;
+        self
+    }
+
+    /// Use Crockford's alphabet, which has no padding.
+    pub fn crockford(mut self) -> Self {
+        self.encoding.alphabet = BASE32_CROCKFORD;
+        self.encoding.padding = Padding::Forbidden;
+        self
+    }
+}
+
+impl<W: Write> EncoderWriter<W, Base16> {
+    /// Write lowercase hex digits.
+    pub fn lowercase(mut self) -> Self {
+        self.encoding.alphabet = BASE16_LOWER;
+        self
+    }
+}
+
+impl<W: Write, B> Write for EncoderWriter<W, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Text left over from a failed write goes first, and if it still
+        // can't be written, none of `buf` is taken.
+        self.write_buf()?;
+        let bits = self.encoding.bits;
+        for &byte in buf {
+            self.acc = (self.acc << 8) | u32::from(byte);
+            self.nbits += 8;
+            while self.nbits >= bits {
+                self.nbits -= bits;
+                let val = (self.acc >> self.nbits) & ((1 << bits) - 1);
+                self.push_char(self.encoding.alphabet[val as usize]);
+            }
+        }
+        // `buf` is now part of the encoder's state, so it has been taken
+        // even if its text can't be written yet. Any error will come up
+        // again on the next write or flush.
+        let _ = self.write_buf();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_buf()?;
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write, B> Drop for EncoderWriter<W, B> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.write_end();
+        }
+    }
+}
+
+/**
+ * Reads text from the wrapped bit source and decodes it. ASCII whitespace is
+ * skipped wherever it appears. Characters outside the alphabet, misplaced or
+ * missing padding, and truncated groups produce errors of kind `InvalidData`.
+ */
+pub struct DecoderReader<R, B> {
+    inner: R,
+    encoding: Encoding,
+    table: [u8; 256],
+    acc: u32,
+    nbits: u32,
+    chars: usize,
+    pads: usize,
+    buf: Vec<u8>,
+    pos: usize,
+    done: bool,
+    _base: PhantomData<B>,
+}
+
+/// Decodes Base64 text.
+pub type Base64Reader<R> = DecoderReader<R, Base64>;
+/// Decodes Base32 text.
+pub type Base32Reader<R> = DecoderReader<R, Base32>;
+/// Decodes Base16 text.
+pub type Base16Reader<R> = DecoderReader<R, Base16>;
+
+impl<R: Read, B: Base> DecoderReader<R, B> {
+    /// Create a reader which decodes text in the standard alphabet with
+    /// padding from `inner`.
+    pub fn new(inner: R) -> Self {
+        let encoding = default_encoding::<B>();
+        Self {
+            inner,
+            encoding,
+            table: encoding.decode_table(),
+            acc: 0,
+            nbits: 0,
+            chars: 0,
+            pads: 0,
+            buf: Vec::new(),
+            pos: 0,
+            done: false,
+            _base: PhantomData,
+        }
+    }
+}
+
+impl<R: Read, B> DecoderReader<R, B> {
+    /// Use the specified padding policy.
+    pub fn padding(mut self, padding: Padding) -> Self {
+        self.encoding.padding = padding;
+        self
+    }
+
+    fn set_alphabet(&mut self, alphabet: &'static [u8]) {
+        self.encoding.alphabet = alphabet;
+        self.table = self.encoding.decode_table();
+    }
+
+    /// Unwrap the reader, returning the underlying bit source.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        Error::new(io::ErrorKind::InvalidData, msg.to_string())
+    }
+
+    fn decode(&mut self, text: &[u8]) -> io::Result<()> {
+        let bits = self.encoding.bits;
+        for &c in text {
+            if c.is_ascii_whitespace() || (self.encoding.crockford && c == b'-') {
+                continue;
+            }
+            if c == PAD && self.encoding.bits != 4 {
+                if self.encoding.padding == Padding::Forbidden {
+                    return Err(self.error("Unexpected padding"));
+                }
+                if self.pads == 0 {
+                    self.check_group_end()?;
+                }
+                // Padding only ever fills out an incomplete group.
+                if self.chars.is_multiple_of(self.encoding.group()) {
+                    return Err(self.error("Too much padding"));
+                }
+                self.pads += 1;
+                self.chars += 1;
+                continue;
+            }
+            if self.pads > 0 {
+                return Err(self.error("Data after padding"));
+            }
+            let val = self.table[c as usize];
+            if val == INVALID {
+                let msg = format!("Invalid character {:?}", c as char);
+                return Err(self.error(&msg));
+            }
+            self.acc = (self.acc << bits) | u32::from(val);
+            self.nbits += bits;
+            self.chars += 1;
+            if self.nbits >= 8 {
+                self.nbits -= 8;
+                self.buf.push((self.acc >> self.nbits) as u8);
+                self.acc &= (1 << self.nbits) - 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the data characters read so far end in a place where a
+    /// group may end, with no leftover bits set.
+    fn check_group_end(&self) -> io::Result<()> {
+        let partial = self.chars % self.encoding.group();
+        if (partial * self.encoding.bits as usize) % 8 >= self.encoding.bits as usize {
+            return Err(self.error("Truncated group"));
+        }
+        if self.acc != 0 {
+            return Err(self.error("Nonzero trailing bits"));
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.pads == 0 {
+            self.check_group_end()?;
+        }
+        let complete = self.chars.is_multiple_of(self.encoding.group());
+        if !complete && (self.pads > 0 || self.encoding.padding == Padding::Required) {
+            return Err(self.error("Incomplete padding"));
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> DecoderReader<R, Base64> {
+    /// Use the URL- and filename-safe alphabet, which has `-` and `_` in place
+    /// of `+` and `/`.
+    pub fn url_safe(mut self) -> Self {
+        self.set_alphabet(BASE64_URL);
+        self
+    }
+}
+
+impl<R: Read> DecoderReader<R, Base32> {
+    /// Use the "base32hex" alphabet, which preserves the sort order of the
+    /// encoded data.
+    pub fn hex_alphabet(mut self) -> Self {
+        self.set_alphabet(BASE32_HEX);
+        self
+    }
+
+    /// Use Crockford's alphabet, which has no padding. As Crockford
+    /// recommends, `O` is read as `0`, `I` and `L` are read as `1`, and
+    /// hyphens are ignored.
+    pub fn crockford(mut self) -> Self {
+        self.encoding.crockford = true;
+        self.encoding.padding = Padding::Forbidden;
+        self.set_alphabet(BASE32_CROCKFORD);
+        self
+    }
+}
+
+impl<R: Read, B> Read for DecoderReader<R, B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut text = [0; 1024];
+        while self.pos == self.buf.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.buf.clear();
+            self.pos = 0;
+            let len = match self.inner.read(&mut text) {
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if len == 0 {
+                self.done = true;
+                self.finish()?;
+            } else {
+                self.decode(&text[..len])?;
+            }
+        }
+        let len = usize::min(buf.len(), self.buf.len() - self.pos);
+        buf[..len].copy_from_slice(&self.buf[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn encode<B: Base>(
+        data: &[u8],
+        configure: impl Fn(EncoderWriter<Vec<u8>, B>) -> EncoderWriter<Vec<u8>, B>,
+    ) -> String {
+        let mut writer = configure(EncoderWriter::new(Vec::new()));
+        for byte in data {
+            writer.write_all(&[*byte]).unwrap();
+        }
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    fn decode<B: Base>(
+        text: &str,
+        configure: impl Fn(DecoderReader<&[u8], B>) -> DecoderReader<&[u8], B>,
+    ) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        configure(DecoderReader::new(text.as_bytes())).read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    #[test]
+    fn round_trips_rfc_4648_vectors() -> io::Result<()> {
+        let vectors = [
+            ("", "", "", ""),
+            ("f", "Zg==", "MY======", "66"),
+            ("fo", "Zm8=", "MZXQ====", "666F"),
+            ("foo", "Zm9v", "MZXW6===", "666F6F"),
+            ("foob", "Zm9vYg==", "MZXW6YQ=", "666F6F62"),
+            ("fooba", "Zm9vYmE=", "MZXW6YTB", "666F6F6261"),
+            ("foobar", "Zm9vYmFy", "MZXW6YTBOI======", "666F6F626172"),
+        ];
+        for &(data, b64, b32, b16) in &vectors {
+            assert_eq!(encode::<Base64>(data.as_bytes(), |w| w), b64);
+            assert_eq!(encode::<Base32>(data.as_bytes(), |w| w), b32);
+            assert_eq!(encode::<Base16>(data.as_bytes(), |w| w), b16);
+            assert_eq!(decode::<Base64>(b64, |r| r)?, data.as_bytes());
+            assert_eq!(decode::<Base32>(b32, |r| r)?, data.as_bytes());
+            assert_eq!(
+                decode::<Base16>(&b16.to_lowercase(), |r| r)?,
+                data.as_bytes()
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn supports_alternate_alphabets() -> io::Result<()> {
+        let data = [0xFB, 0xFF, 0x10, 0x42];
+        assert_eq!(
+            encode::<Base64>(&data, |w| w.url_safe().padding(Padding::Forbidden)),
+            "-_8QQg"
+        );
+        assert_eq!(
+            decode::<Base64>("-_8Q\nQg", |r| r.url_safe().padding(Padding::Optional))?,
+            data
+        );
+        assert_eq!(
+            encode::<Base32>(b"foobar", |w| w.hex_alphabet()),
+            "CPNMUOJ1E8======"
+        );
+        assert_eq!(encode::<Base32>(b"foobar", |w| w.crockford()), "CSQPYRK1E8");
+        assert_eq!(
+            decode::<Base32>("csqp-yrkie8", |r| r.crockford())?,
+            b"foobar"
+        );
+        assert_eq!(
+            encode::<Base16>(&data, |w| w.lowercase().wrap(Some(3))),
+            "fbf\nf10\n42"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_malformed_text() {
+        for &text in &[
+            "Zm9", "Zm9v=", "Zg=", "Zh==", "Zg==Zg==", "Z!==", "Zm9v====", "Zg======", "====",
+            "Zm9vYg===",
+        ] {
+            let err = decode::<Base64>(text, |r| r).expect_err(text);
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", text);
+        }
+        for &text in &["MZXW6====", "MZXW6YQ==", "MZXW6YTB========", "========"] {
+            let err = decode::<Base32>(text, |r| r).expect_err(text);
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", text);
+        }
+        assert!(decode::<Base64>("Zg", |r| r).is_err());
+        assert_eq!(
+            decode::<Base64>("Zg", |r| r.padding(Padding::Optional)).unwrap(),
+            b"f"
+        );
+        assert!(decode::<Base64>("Zg==", |r| r.padding(Padding::Forbidden)).is_err());
+        assert!(decode::<Base16>("ABC", |r| r).is_err());
+    }
+
+    #[test]
+    fn keeps_text_after_a_failed_write() -> io::Result<()> {
+        /// A sink that fails its first write.
+        struct FailOnce(Vec<u8>, bool);
+
+        impl Write for FailOnce {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if !self.1 {
+                    self.1 = true;
+                    return Err(io::Error::other("Not yet"));
+                }
+                self.0.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut out = Base64Writer::new(FailOnce(Vec::new(), false));
+        out.write_all(b"foo")?;
+        out.write_all(b"bar")?;
+        assert_eq!(out.finish()?.0, b"Zm9vYmFy");
+
+        let mut out = Base64Writer::new(FailOnce(Vec::new(), false));
+        out.write_all(b"foo")?;
+        assert!(out.flush().is_ok());
+        assert_eq!(out.finish()?.0, b"Zm9v");
+        Ok(())
+    }
+}
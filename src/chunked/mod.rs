@@ -0,0 +1,462 @@
+//! Adapters for the chunked transfer coding of HTTP/1.1, as described in
+//! [RFC 9112, section 7.1].
+//!
+//! A chunked body is a series of chunks, each a hexadecimal size on a line of
+//! its own followed by that many bytes and a CRLF. A chunk of size zero ends
+//! the body, and is followed by any trailer fields and a blank line.
+//!
+//! [RFC 9112, section 7.1]: https://www.rfc-editor.org/rfc/rfc9112#section-7.1
+
+use std::{
+    convert::TryFrom,
+    io::{self, BufRead, Error, Read, Write},
+    mem,
+};
+
+/// The longest line accepted for a chunk size or trailer field, not counting
+/// the line ending.
+pub const MAX_LINE_LEN: usize = 8 * 1024;
+
+/// The most bytes of trailer fields accepted after the last chunk.
+pub const MAX_TRAILERS_LEN: usize = 64 * 1024;
+
+fn invalid(msg: String) -> Error {
+    Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    /// Expecting a chunk-size line.
+    Size,
+    /// In the middle of a chunk with this many bytes left.
+    Data(u64),
+    /// Expecting the CRLF after a chunk.
+    DataEnd,
+    /// Reading the trailer fields, of which this many bytes have been read.
+    Trailers(usize),
+    /// The body and trailers have been read.
+    Done,
+    /// The body turned out to be malformed.
+    Failed,
+}
+
+/**
+ * A bit source that decodes a chunked body. Reads return the data of the
+ * chunks, and report the end of the source after the last chunk. Its
+ * trailer fields are then available from [`trailers`], and the underlying
+ * bit source is positioned just after the body.
+ *
+ * Returns an error of kind `InvalidData` if the body is malformed, including
+ * if a chunk size has more digits than fit in a `u64` or a line is longer
+ * than [`MAX_LINE_LEN`], and of kind `UnexpectedEof` if the underlying bit
+ * source is exhausted before the end of the body. Chunk extensions are
+ * ignored, and lines may end in a bare LF. After an `InvalidData` error,
+ * every later read fails too. After any other error, such as `WouldBlock`,
+ * the next read picks up where the last one left off.
+ *
+ * ```
+ * # use extended_io::chunked::ChunkedReader;
+ * # use std::io::Read;
+ * let body = b"5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\nExpires: never\r\n\r\nnext";
+ * let mut src = ChunkedReader::new(&body[..]);
+ * let mut text = String::new();
+ * src.read_to_string(&mut text)?;
+ * assert_eq!(text, "hello, world");
+ * assert_eq!(src.trailers(), [("Expires".to_string(), "never".to_string())]);
+ * assert_eq!(src.into_inner(), b"next");
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ *
+ * [`trailers`]: #method.trailers
+ * [`MAX_LINE_LEN`]: constant.MAX_LINE_LEN.html
+ */
+#[derive(Debug)]
+pub struct ChunkedReader<R> {
+    inner: R,
+    state: State,
+    /// The part of the current line received so far.
+    line: Vec<u8>,
+    /// The trailer fields received so far, until the blank line after them.
+    pending_trailers: Vec<(String, String)>,
+    trailers: Vec<(String, String)>,
+}
+
+impl<R: BufRead> ChunkedReader<R> {
+    /// Decode the chunked body read from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            state: State::Size,
+            line: Vec::new(),
+            pending_trailers: Vec::new(),
+            trailers: Vec::new(),
+        }
+    }
+
+    /// The trailer fields, as name-value pairs in the order they were sent.
+    /// Empty until the end of the body has been read.
+    pub fn trailers(&self) -> &[(String, String)] {
+        &self.trailers
+    }
+
+    /// Whether the end of the body and its trailers have been read.
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Get a reference to the underlying bit source.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying bit source.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwrap the reader, returning the underlying bit source.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Read a line of at most `MAX_LINE_LEN` bytes, without its line ending.
+    /// The bytes received before an error are kept for the next call.
+    fn read_line(&mut self) -> io::Result<Vec<u8>> {
+        let limit = MAX_LINE_LEN as u64 + 2;
+        let left = limit - self.line.len() as u64;
+        (&mut self.inner)
+            .take(left)
+            .read_until(b'\n', &mut self.line)?;
+        if self.line.last() != Some(&b'\n') {
+            if self.line.len() as u64 == limit {
+                return Err(invalid(format!(
+                    "Line in chunked body is longer than {} bytes",
+                    MAX_LINE_LEN,
+                )));
+            }
+            return Err(Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Chunked body cut short",
+            ));
+        }
+        let mut line = mem::take(&mut self.line);
+        line.pop();
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        Ok(line)
+    }
+
+    fn read_size(&mut self) -> io::Result<u64> {
+        let line = self.read_line()?;
+        let end = line.iter().position(|&b| b == b';').unwrap_or(line.len());
+        let size = line[..end].trim_ascii();
+        let parsed = std::str::from_utf8(size)
+            .ok()
+            .filter(|size| !size.is_empty() && size.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|size| u64::from_str_radix(size, 16).ok());
+        parsed.ok_or_else(|| {
+            let shown = String::from_utf8_lossy(&line[..end.min(32)]);
+            invalid(format!("Invalid chunk size {:?}", shown))
+        })
+    }
+
+    fn read_trailers(&mut self, mut total: usize) -> io::Result<()> {
+        loop {
+            let line = self.read_line()?;
+            if line.is_empty() {
+                self.trailers = mem::take(&mut self.pending_trailers);
+                self.state = State::Done;
+                return Ok(());
+            }
+            total += line.len();
+            if total > MAX_TRAILERS_LEN {
+                let msg = format!("Trailers are longer than {} bytes", MAX_TRAILERS_LEN);
+                return Err(invalid(msg));
+            }
+            let line = String::from_utf8(line)
+                .map_err(|_| invalid("Trailer field isn't valid UTF-8".to_string()))?;
+            let field = line.split_once(':').filter(|(name, _)| {
+                !name.is_empty() && !name.bytes().any(|b| b.is_ascii_whitespace())
+            });
+            match field {
+                Some((name, value)) => {
+                    let value = value.trim_matches(|c| c == ' ' || c == '\t');
+                    let field = (name.to_string(), value.to_string());
+                    self.pending_trailers.push(field);
+                    self.state = State::Trailers(total);
+                }
+                None => return Err(invalid(format!("Invalid trailer field {:?}", line))),
+            }
+        }
+    }
+}
+
+impl<R: BufRead> ChunkedReader<R> {
+    fn read_body(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.state {
+                State::Size => match self.read_size()? {
+                    0 => self.state = State::Trailers(0),
+                    size => self.state = State::Data(size),
+                },
+                State::Data(left) => {
+                    let max = buf.len().min(usize::try_from(left).unwrap_or(usize::MAX));
+                    let n = self.inner.read(&mut buf[..max])?;
+                    if n == 0 {
+                        return Err(Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "Chunked body cut short",
+                        ));
+                    }
+                    self.state = match left - n as u64 {
+                        0 => State::DataEnd,
+                        left => State::Data(left),
+                    };
+                    return Ok(n);
+                }
+                State::DataEnd => {
+                    if !self.read_line()?.is_empty() {
+                        return Err(invalid("Chunk is longer than its size".to_string()));
+                    }
+                    self.state = State::Size;
+                }
+                State::Trailers(total) => self.read_trailers(total)?,
+                State::Done => return Ok(0),
+                State::Failed => {
+                    return Err(invalid("Chunked body is malformed".to_string()));
+                }
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Read for ChunkedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let result = self.read_body(buf);
+        if let Err(e) = &result {
+            if e.kind() == io::ErrorKind::InvalidData {
+                self.state = State::Failed;
+                self.line.clear();
+                self.pending_trailers.clear();
+            }
+        }
+        result
+    }
+}
+
+/**
+ * A bit sink that encodes what's written to it as a chunked body. Each
+ * non-empty write becomes one chunk, so small writes should be gathered by a
+ * `BufWriter` first. The body must be ended with [`finish`], which writes the
+ * last chunk and any trailer fields.
+ *
+ * ```
+ * # use extended_io::chunked::ChunkedWriter;
+ * # use std::io::Write;
+ * let mut out = ChunkedWriter::new(Vec::new());
+ * out.write_all(b"hello, world")?;
+ * let body = out.finish(&[("Expires", "never")])?;
+ * assert_eq!(body, b"C\r\nhello, world\r\n0\r\nExpires: never\r\n\r\n");
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ *
+ * [`finish`]: #method.finish
+ */
+#[derive(Debug)]
+pub struct ChunkedWriter<W: Write> {
+    inner: W,
+    /// Encoded bytes that haven't been written to the bit sink yet.
+    out: Vec<u8>,
+}
+
+impl<W: Write> ChunkedWriter<W> {
+    /// Encode a chunked body to `inner`.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            out: Vec::new(),
+        }
+    }
+
+    /// Get a reference to the underlying bit sink.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying bit sink. Part of a chunk
+    /// may be held back if the bit sink failed to accept it.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Write out the encoded bytes, keeping whatever the bit sink didn't
+    /// accept if it fails.
+    fn write_out(&mut self) -> io::Result<()> {
+        while !self.out.is_empty() {
+            match self.inner.write(&self.out) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.out.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * End the body with the last chunk and the specified trailer fields, and
+     * return the underlying bit sink.
+     *
+     * Returns an error of kind `InvalidInput`, before writing anything, if a
+     * field name is empty or contains whitespace or `:`, or if a value
+     * contains a line break.
+     */
+    pub fn finish(mut self, trailers: &[(&str, &str)]) -> io::Result<W> {
+        for (name, value) in trailers {
+            let bad_name =
+                name.is_empty() || name.bytes().any(|b| b == b':' || b.is_ascii_whitespace());
+            if bad_name || value.contains(['\r', '\n']) {
+                let msg = format!("Invalid trailer field {:?}: {:?}", name, value);
+                return Err(Error::new(io::ErrorKind::InvalidInput, msg));
+            }
+        }
+        self.out.extend_from_slice(b"0\r\n");
+        for (name, value) in trailers {
+            let field = format!("{}: {}\r\n", name, value);
+            self.out.extend_from_slice(field.as_bytes());
+        }
+        self.out.extend_from_slice(b"\r\n");
+        self.write_out()?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ChunkedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Nothing of `buf` is taken if an earlier chunk still can't be
+        // written.
+        self.write_out()?;
+        if !buf.is_empty() {
+            self.out
+                .extend_from_slice(format!("{:X}\r\n", buf.len()).as_bytes());
+            self.out.extend_from_slice(buf);
+            self.out.extend_from_slice(b"\r\n");
+            // The chunk is now held by the writer, and any error comes up
+            // again on the next write or flush.
+            let _ = self.write_out();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_out()?;
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{Flaky, Trickle};
+    use std::{cell::Cell, io::BufReader};
+
+    #[test]
+    fn round_trips_bodies() -> io::Result<()> {
+        let data = (0..1000).map(|x| (x % 251) as u8).collect::<Vec<_>>();
+        let mut out = ChunkedWriter::new(Vec::new());
+        for piece in data.chunks(300) {
+            out.write_all(piece)?;
+        }
+        crate::write_u16(&mut out, 42)?;
+        let body = out.finish(&[("Digest", "sha-256=abc"), ("X-Count", "4")])?;
+        // The body arrives a few bytes at a time.
+        let mut src = ChunkedReader::new(BufReader::with_capacity(3, &body[..]));
+        assert_eq!(crate::read_bytes(&mut src, 1000)?, data);
+        assert!(!src.is_done());
+        assert_eq!(crate::read_u16(&mut src)?, 42);
+        assert_eq!(src.read(&mut [0; 8])?, 0);
+        assert!(src.is_done());
+        let trailers = src
+            .trailers()
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(trailers, [("Digest", "sha-256=abc"), ("X-Count", "4")]);
+
+        let out = ChunkedWriter::new(Vec::new());
+        let err = out
+            .finish(&[("Bad Name", "x")])
+            .expect_err("Wrote a bad trailer");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_malformed_bodies() {
+        let long_line = format!("1{}\r\n", " ".repeat(MAX_LINE_LEN));
+        for bad in [
+            "fffffffffffffffff\r\n",
+            "+5\r\nhello\r\n0\r\n\r\n",
+            "zz\r\n",
+            "\r\n",
+            "3\r\nhello\r\n0\r\n\r\n",
+            "0\r\nno colon\r\n\r\n",
+            long_line.as_str(),
+        ] {
+            let err = ChunkedReader::new(bad.as_bytes())
+                .read_to_end(&mut Vec::new())
+                .expect_err("Accepted a malformed body");
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", bad);
+        }
+        let err = ChunkedReader::new(&b"5\r\nhel"[..])
+            .read_to_end(&mut Vec::new())
+            .expect_err("Accepted a truncated body");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut src = ChunkedReader::new(&b"0\r\nA: b\r\nno colon\r\n\r\n"[..]);
+        assert!(src.read(&mut [0; 8]).is_err());
+        assert!(src.trailers().is_empty());
+        let err = src.read(&mut [0; 8]).expect_err("Read on after an error");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn resumes_after_would_block() -> io::Result<()> {
+        let body = b"5\r\nhello\r\n0\r\nA: b\r\nC: d\r\n\r\n";
+        let mut src = ChunkedReader::new(BufReader::with_capacity(1, Trickle::blocking(body)));
+        let mut data = Vec::new();
+        loop {
+            match src.read_to_end(&mut data) {
+                Ok(_) => break,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    assert!(src.trailers().is_empty());
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        assert_eq!(data, b"hello");
+        assert_eq!(src.trailers().len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn writes_whole_chunks_despite_sink_errors() -> io::Result<()> {
+        let failing = Cell::new(true);
+        let mut out = ChunkedWriter::new(Flaky::new(4, &failing));
+        for piece in [&b"hello"[..], b", ", b"world"] {
+            while out.write(piece).is_err() {}
+        }
+        while out.flush().is_err() {}
+        failing.set(false);
+        let body = out.finish(&[])?.data;
+        assert_eq!(body, b"5\r\nhello\r\n2\r\n, \r\n5\r\nworld\r\n0\r\n\r\n");
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+//! A decoder for the crate's primitive types that reads straight out of the
+//! buffer of a [`BufRead`] instead of copying through `read_exact`.
+//!
+//! [`BufRead`]: /std/io/trait.BufRead.html
+
+use std::io::{self, BufRead, Read};
+
+use crate::{
+    swap::{self, SwapBytes},
+    ByteOrder,
+};
+
+/**
+ * A wrapper around a buffered bit source with methods that mirror the
+ * crate's `read_*` functions. Whenever the source already has enough bytes
+ * buffered, a value is decoded directly from [`fill_buf`] and then
+ * [`consume`]d, which avoids both the dynamic dispatch and the intermediate
+ * `read_exact` of the free functions. Only a value that straddles the end of
+ * the buffer takes the slow path.
+ *
+ * `BufDecoder` also implements `Read` and `BufRead`, so it can be handed to
+ * anything else that reads from the same source.
+ *
+ * ```
+ * # use extended_io::decoder::BufDecoder;
+ * let mut src = BufDecoder::new(&b"\x00\x2a\x01\x00\x00\x00"[..]);
+ * assert_eq!(src.read_u16()?, 42);
+ * assert_eq!(src.read_u32_le()?, 1);
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ *
+ * [`fill_buf`]: /std/io/trait.BufRead.html#tymethod.fill_buf
+ * [`consume`]: /std/io/trait.BufRead.html#tymethod.consume
+ */
+#[derive(Debug)]
+pub struct BufDecoder<R> {
+    inner: R,
+}
+
+macro_rules! decode {
+    ($($name:ident -> $t:ty: $from:ident;)*) => {
+        $(
+            #[doc = concat!(
+                "Decode a value in the same way as [`", stringify!($name),
+                "`](../fn.", stringify!($name), ".html).",
+            )]
+            pub fn $name(&mut self) -> io::Result<$t> {
+                Ok(<$t>::$from(self.take()?))
+            }
+        )*
+    };
+}
+
+impl<R: BufRead> BufDecoder<R> {
+    /// Decode values from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self { inner }
+    }
+
+    /// Get a reference to the underlying bit source.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying bit source.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwrap the decoder, returning the underlying bit source.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Read the next `N` bytes, straight from the buffer if it holds enough.
+    #[inline]
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut out = [0; N];
+        loop {
+            match self.inner.fill_buf() {
+                Ok(buf) if buf.len() >= N => {
+                    out.copy_from_slice(&buf[..N]);
+                    self.inner.consume(N);
+                }
+                Ok(_) => self.inner.read_exact(&mut out)?,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+            return Ok(out);
+        }
+    }
+
+    decode! {
+        read_u8 -> u8: from_be_bytes;
+        read_u8_le -> u8: from_le_bytes;
+        read_u8_ne -> u8: from_ne_bytes;
+        read_i8 -> i8: from_be_bytes;
+        read_i8_le -> i8: from_le_bytes;
+        read_i8_ne -> i8: from_ne_bytes;
+        read_u16 -> u16: from_be_bytes;
+        read_u16_le -> u16: from_le_bytes;
+        read_u16_ne -> u16: from_ne_bytes;
+        read_i16 -> i16: from_be_bytes;
+        read_i16_le -> i16: from_le_bytes;
+        read_i16_ne -> i16: from_ne_bytes;
+        read_u32 -> u32: from_be_bytes;
+        read_u32_le -> u32: from_le_bytes;
+        read_u32_ne -> u32: from_ne_bytes;
+        read_i32 -> i32: from_be_bytes;
+        read_i32_le -> i32: from_le_bytes;
+        read_i32_ne -> i32: from_ne_bytes;
+        read_u64 -> u64: from_be_bytes;
+        read_u64_le -> u64: from_le_bytes;
+        read_u64_ne -> u64: from_ne_bytes;
+        read_i64 -> i64: from_be_bytes;
+        read_i64_le -> i64: from_le_bytes;
+        read_i64_ne -> i64: from_ne_bytes;
+        read_u128 -> u128: from_be_bytes;
+        read_u128_le -> u128: from_le_bytes;
+        read_u128_ne -> u128: from_ne_bytes;
+        read_i128 -> i128: from_be_bytes;
+        read_i128_le -> i128: from_le_bytes;
+        read_i128_ne -> i128: from_ne_bytes;
+        read_f32 -> f32: from_be_bytes;
+        read_f32_le -> f32: from_le_bytes;
+        read_f32_ne -> f32: from_ne_bytes;
+        read_f64 -> f64: from_be_bytes;
+        read_f64_le -> f64: from_le_bytes;
+        read_f64_ne -> f64: from_ne_bytes;
+    }
+
+    /// Decode values in the same way as [`read_bytes`](../fn.read_bytes.html).
+    pub fn read_bytes(&mut self, length: u64) -> io::Result<Vec<u8>> {
+        crate::read_bytes(&mut self.inner, length)
+    }
+
+    /// Fill `dst` in the same way as [`read_slice`](../swap/fn.read_slice.html).
+    pub fn read_slice<T: SwapBytes>(&mut self, dst: &mut [T], order: ByteOrder) -> io::Result<()> {
+        swap::read_slice(&mut self.inner, dst, order)
+    }
+}
+
+impl<R: BufRead> Read for BufDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl<R: BufRead> BufRead for BufDecoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::BufReader;
+
+    #[test]
+    fn decodes_across_buffer_boundaries() -> io::Result<()> {
+        let mut data = Vec::new();
+        for x in 0..100u32 {
+            crate::write_u32(&mut data, x)?;
+            crate::write_u16_le(&mut data, x as u16)?;
+            crate::write_f64(&mut data, x.into())?;
+        }
+        // A 7-byte buffer forces most values onto the slow path at some point.
+        let mut src = BufDecoder::new(BufReader::with_capacity(7, &data[..]));
+        for x in 0..100u32 {
+            assert_eq!(src.read_u32()?, x);
+            assert_eq!(src.read_u16_le()?, x as u16);
+            assert_eq!(src.read_f64()?, x.into());
+        }
+        let err = src.read_u8().expect_err("Read past the end of the source");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        Ok(())
+    }
+
+    #[test]
+    fn leaves_the_rest_for_other_readers() -> io::Result<()> {
+        let mut src = BufDecoder::new(&b"\xff\xfeline\n"[..]);
+        assert_eq!(src.read_i16_ne()?, i16::from_ne_bytes([0xff, 0xfe]));
+        assert_eq!(crate::read_t::<String>(&mut src)?, "line");
+        Ok(())
+    }
+
+    #[test]
+    fn passes_on_source_errors() -> io::Result<()> {
+        /// A source whose buffer fails with each of `errors` before filling.
+        struct Flaky {
+            data: &'static [u8],
+            errors: Vec<io::ErrorKind>,
+        }
+
+        impl Read for Flaky {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let n = self.fill_buf()?.read(buf)?;
+                self.consume(n);
+                Ok(n)
+            }
+        }
+
+        impl BufRead for Flaky {
+            fn fill_buf(&mut self) -> io::Result<&[u8]> {
+                match self.errors.pop() {
+                    Some(kind) => Err(kind.into()),
+                    None => Ok(self.data),
+                }
+            }
+
+            fn consume(&mut self, amt: usize) {
+                self.data = &self.data[amt..];
+            }
+        }
+
+        let mut src = BufDecoder::new(Flaky {
+            data: b"\x00\x01\x00\x02",
+            errors: vec![io::ErrorKind::Interrupted, io::ErrorKind::TimedOut],
+        });
+        let err = src.read_u16().expect_err("Ignored a source error");
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(src.read_u16()?, 1);
+        assert_eq!(src.read_u16()?, 2);
+        Ok(())
+    }
+}
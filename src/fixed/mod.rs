@@ -0,0 +1,436 @@
+//! Conversions between floating-point values and the fixed-point (Q-format)
+//! and normalized integer (unorm/snorm) encodings used by DSP and graphics
+//! formats. The raw integers are read and written with the crate's primitive
+//! integer functions, so every [`ByteOrder`] is supported.
+//!
+//! [`ByteOrder`]: ../enum.ByteOrder.html
+
+use std::io::{self, Read, Write};
+
+use crate::{read_uint, write_uint, ByteOrder};
+
+/// How a value that falls between two representable values is rounded when it
+/// is encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rounding {
+    /// Round to the nearest representable value, with ties rounded away from
+    /// zero.
+    Nearest,
+    /// Round to the nearest representable value, with ties rounded to the
+    /// even value.
+    NearestEven,
+    /// Round towards negative infinity.
+    Floor,
+    /// Round towards positive infinity.
+    Ceil,
+    /// Round towards zero.
+    TowardZero,
+}
+
+impl Rounding {
+    fn apply(self, val: f64) -> f64 {
+        match self {
+            Self::Nearest => val.round(),
+            Self::NearestEven => val.round_ties_even(),
+            Self::Floor => val.floor(),
+            Self::Ceil => val.ceil(),
+            Self::TowardZero => val.trunc(),
+        }
+    }
+}
+
+/// The integer that holds an encoded value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Storage {
+    signed: bool,
+    bits: u32,
+}
+
+impl Storage {
+    const fn new(signed: bool, bits: u32) -> Self {
+        assert!(
+            bits == 8 || bits == 16 || bits == 32 || bits == 64,
+            "Storage must be 8, 16, 32, or 64 bits wide",
+        );
+        Self { signed, bits }
+    }
+
+    fn width(self) -> usize {
+        self.bits as usize / 8
+    }
+
+    fn min(self) -> i128 {
+        if self.signed {
+            -(1 << (self.bits - 1))
+        } else {
+            0
+        }
+    }
+
+    fn max(self) -> i128 {
+        if self.signed {
+            (1 << (self.bits - 1)) - 1
+        } else {
+            (1 << self.bits) - 1
+        }
+    }
+
+    /// Interpret the low `bits` bits of `raw` as an integer.
+    fn widen(self, raw: u64) -> i128 {
+        let shift = 64 - self.bits;
+        if self.signed {
+            (((raw << shift) as i64) >> shift).into()
+        } else {
+            ((raw << shift) >> shift).into()
+        }
+    }
+
+    /// Round `scaled` to an integer and fit it into this storage. Returns
+    /// `None` if `scaled` is NaN or if it is out of range and `saturate` is
+    /// not set.
+    fn quantize(self, scaled: f64, rounding: Rounding, saturate: bool) -> Option<u64> {
+        if scaled.is_nan() {
+            return None;
+        }
+        // Float-to-int casts saturate, and every storage range fits well
+        // inside an `i128`, so the range check below is exact.
+        let rounded = rounding.apply(scaled) as i128;
+        let clamped = rounded.clamp(self.min(), self.max());
+        if clamped != rounded && !saturate {
+            None
+        } else {
+            Some(clamped as u64 & (u64::MAX >> (64 - self.bits)))
+        }
+    }
+}
+
+/**
+ * A binary fixed-point format with a configurable number of integer and
+ * fraction bits. For signed formats the integer bits include the sign bit, so
+ * Q15 (one sign bit and fifteen fraction bits in an `i16`) is
+ * `QFormat::signed(1, 15)` and Q16.16 is `QFormat::signed(16, 16)`. The total
+ * number of bits must be 8, 16, 32, or 64.
+ *
+ * Values are encoded with [`Rounding::Nearest`] and saturate when they are out
+ * of range unless configured otherwise.
+ *
+ * [`Rounding::Nearest`]: enum.Rounding.html#variant.Nearest
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct QFormat {
+    storage: Storage,
+    frac_bits: u32,
+    rounding: Rounding,
+    saturate: bool,
+}
+
+impl QFormat {
+    /// Signed Q7: one sign bit and seven fraction bits.
+    pub const Q7: Self = Self::signed(1, 7);
+    /// Signed Q15: one sign bit and fifteen fraction bits.
+    pub const Q15: Self = Self::signed(1, 15);
+    /// Signed Q31: one sign bit and thirty-one fraction bits.
+    pub const Q31: Self = Self::signed(1, 31);
+    /// Signed Q16.16: sixteen integer bits (including the sign bit) and
+    /// sixteen fraction bits.
+    pub const Q16_16: Self = Self::signed(16, 16);
+
+    /// A signed format with `int_bits` integer bits (including the sign bit)
+    /// and `frac_bits` fraction bits.
+    ///
+    /// # Panics
+    /// Panics if `int_bits + frac_bits` is not 8, 16, 32, or 64.
+    pub const fn signed(int_bits: u32, frac_bits: u32) -> Self {
+        Self::new(true, int_bits, frac_bits)
+    }
+
+    /// An unsigned format with `int_bits` integer bits and `frac_bits`
+    /// fraction bits.
+    ///
+    /// # Panics
+    /// Panics if `int_bits + frac_bits` is not 8, 16, 32, or 64.
+    pub const fn unsigned(int_bits: u32, frac_bits: u32) -> Self {
+        Self::new(false, int_bits, frac_bits)
+    }
+
+    const fn new(signed: bool, int_bits: u32, frac_bits: u32) -> Self {
+        Self {
+            storage: Storage::new(signed, int_bits + frac_bits),
+            frac_bits,
+            rounding: Rounding::Nearest,
+            saturate: true,
+        }
+    }
+
+    /// Use the specified rounding mode when encoding.
+    pub const fn with_rounding(mut self, rounding: Rounding) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Clamp out-of-range values to the nearest representable value when
+    /// encoding if `saturate` is set; otherwise reject them.
+    pub const fn with_saturation(mut self, saturate: bool) -> Self {
+        self.saturate = saturate;
+        self
+    }
+
+    /// The number of bytes in an encoded value.
+    pub fn width(&self) -> usize {
+        self.storage.width()
+    }
+
+    fn scale(&self) -> f64 {
+        2f64.powi(self.frac_bits as i32)
+    }
+
+    /// Decode the value held in the low bits of `raw`.
+    pub fn decode(&self, raw: u64) -> f64 {
+        self.storage.widen(raw) as f64 / self.scale()
+    }
+
+    /// Encode `val`, returning the raw bits in the low bits of the result.
+    /// Returns `None` if `val` is NaN or if it is out of range and this format
+    /// doesn't saturate.
+    pub fn encode(&self, val: f64) -> Option<u64> {
+        self.storage
+            .quantize(val * self.scale(), self.rounding, self.saturate)
+    }
+}
+
+/**
+ * A normalized integer format, which maps the full range of an unsigned
+ * integer onto `[0.0, 1.0]` (unorm) or the range of a signed integer onto
+ * `[-1.0, 1.0]` (snorm). As in graphics APIs, the most negative snorm value
+ * decodes to -1.0, the same as its successor.
+ *
+ * Values are encoded with [`Rounding::Nearest`] and saturate when they are out
+ * of range unless configured otherwise.
+ *
+ * [`Rounding::Nearest`]: enum.Rounding.html#variant.Nearest
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NormFormat {
+    storage: Storage,
+    rounding: Rounding,
+    saturate: bool,
+}
+
+impl NormFormat {
+    /// An 8-bit unsigned normalized integer.
+    pub const UNORM8: Self = Self::unorm(8);
+    /// A 16-bit unsigned normalized integer.
+    pub const UNORM16: Self = Self::unorm(16);
+    /// An 8-bit signed normalized integer.
+    pub const SNORM8: Self = Self::snorm(8);
+    /// A 16-bit signed normalized integer.
+    pub const SNORM16: Self = Self::snorm(16);
+
+    /// An unsigned normalized integer `bits` bits wide.
+    ///
+    /// # Panics
+    /// Panics if `bits` is not 8, 16, 32, or 64.
+    pub const fn unorm(bits: u32) -> Self {
+        Self::new(false, bits)
+    }
+
+    /// A signed normalized integer `bits` bits wide.
+    ///
+    /// # Panics
+    /// Panics if `bits` is not 8, 16, 32, or 64.
+    pub const fn snorm(bits: u32) -> Self {
+        Self::new(true, bits)
+    }
+
+    const fn new(signed: bool, bits: u32) -> Self {
+        Self {
+            storage: Storage::new(signed, bits),
+            rounding: Rounding::Nearest,
+            saturate: true,
+        }
+    }
+
+    /// Use the specified rounding mode when encoding.
+    pub const fn with_rounding(mut self, rounding: Rounding) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Clamp out-of-range values to the nearest representable value when
+    /// encoding if `saturate` is set; otherwise reject them.
+    pub const fn with_saturation(mut self, saturate: bool) -> Self {
+        self.saturate = saturate;
+        self
+    }
+
+    /// The number of bytes in an encoded value.
+    pub fn width(&self) -> usize {
+        self.storage.width()
+    }
+
+    /// Decode the value held in the low bits of `raw`.
+    pub fn decode(&self, raw: u64) -> f64 {
+        let val = self.storage.widen(raw) as f64 / self.storage.max() as f64;
+        val.max(-1.0)
+    }
+
+    /// Encode `val`, returning the raw bits in the low bits of the result.
+    /// Returns `None` if `val` is NaN or if it is out of range and this format
+    /// doesn't saturate.
+    pub fn encode(&self, val: f64) -> Option<u64> {
+        let (min, max) = if self.storage.signed {
+            (-1.0, 1.0)
+        } else {
+            (0.0, 1.0)
+        };
+        if !self.saturate && !(min..=max).contains(&val) {
+            return None;
+        }
+        let val = val.clamp(min, max);
+        self.storage
+            .quantize(val * self.storage.max() as f64, self.rounding, true)
+    }
+}
+
+fn out_of_range(val: f64) -> io::Error {
+    let msg = format!("{} can't be represented in the requested format", val);
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Read a fixed-point value in the specified format and byte order from the
+/// specified bit source.
+pub fn read_q_f32(src: &mut dyn Read, format: QFormat, order: ByteOrder) -> io::Result<f32> {
+    Ok(read_q_f64(src, format, order)? as f32)
+}
+
+/// Read a fixed-point value in the specified format and byte order from the
+/// specified bit source.
+pub fn read_q_f64(src: &mut dyn Read, format: QFormat, order: ByteOrder) -> io::Result<f64> {
+    Ok(format.decode(read_uint(src, format.width(), order)?))
+}
+
+/// Write a value as fixed-point in the specified format and byte order to the
+/// specified bit sink.
+pub fn write_q_f32(
+    out: &mut dyn Write,
+    format: QFormat,
+    order: ByteOrder,
+    val: f32,
+) -> io::Result<()> {
+    write_q_f64(out, format, order, val.into())
+}
+
+/// Write a value as fixed-point in the specified format and byte order to the
+/// specified bit sink.
+pub fn write_q_f64(
+    out: &mut dyn Write,
+    format: QFormat,
+    order: ByteOrder,
+    val: f64,
+) -> io::Result<()> {
+    let raw = format.encode(val).ok_or_else(|| out_of_range(val))?;
+    write_uint(out, format.width(), order, raw)
+}
+
+/// Read a normalized integer in the specified format and byte order from the
+/// specified bit source.
+pub fn read_norm_f32(src: &mut dyn Read, format: NormFormat, order: ByteOrder) -> io::Result<f32> {
+    Ok(read_norm_f64(src, format, order)? as f32)
+}
+
+/// Read a normalized integer in the specified format and byte order from the
+/// specified bit source.
+pub fn read_norm_f64(src: &mut dyn Read, format: NormFormat, order: ByteOrder) -> io::Result<f64> {
+    Ok(format.decode(read_uint(src, format.width(), order)?))
+}
+
+/// Write a value as a normalized integer in the specified format and byte
+/// order to the specified bit sink.
+pub fn write_norm_f32(
+    out: &mut dyn Write,
+    format: NormFormat,
+    order: ByteOrder,
+    val: f32,
+) -> io::Result<()> {
+    write_norm_f64(out, format, order, val.into())
+}
+
+/// Write a value as a normalized integer in the specified format and byte
+/// order to the specified bit sink.
+pub fn write_norm_f64(
+    out: &mut dyn Write,
+    format: NormFormat,
+    order: ByteOrder,
+    val: f64,
+) -> io::Result<()> {
+    let raw = format.encode(val).ok_or_else(|| out_of_range(val))?;
+    write_uint(out, format.width(), order, raw)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Cursor;
+
+    #[test]
+    fn q15_round_trips() -> io::Result<()> {
+        let mut c = Cursor::new(Vec::new());
+        write_q_f64(&mut c, QFormat::Q15, ByteOrder::Big, -0.5)?;
+        write_q_f32(&mut c, QFormat::Q15, ByteOrder::Little, 0.25)?;
+        assert_eq!(c.get_ref(), &[0xC0, 0x00, 0x00, 0x20]);
+        c.set_position(0);
+        assert_eq!(read_q_f64(&mut c, QFormat::Q15, ByteOrder::Big)?, -0.5);
+        assert_eq!(read_q_f32(&mut c, QFormat::Q15, ByteOrder::Little)?, 0.25);
+        Ok(())
+    }
+
+    #[test]
+    fn q16_16_rounds_and_saturates() {
+        let q = QFormat::Q16_16;
+        assert_eq!(q.encode(1.5), Some(0x0001_8000));
+        assert_eq!(q.encode(-1.0), Some(0xFFFF_0000));
+        assert_eq!(q.encode(1e9), Some(0x7FFF_FFFF));
+        assert_eq!(q.with_saturation(false).encode(1e9), None);
+        let half_lsb = 0.5 / 65536.0;
+        assert_eq!(q.encode(half_lsb), Some(1));
+        assert_eq!(
+            q.with_rounding(Rounding::NearestEven).encode(half_lsb),
+            Some(0)
+        );
+        assert_eq!(
+            q.with_rounding(Rounding::Floor).encode(-half_lsb),
+            Some(0xFFFF_FFFF)
+        );
+        assert_eq!(q.encode(f64::NAN), None);
+    }
+
+    #[test]
+    fn norm_formats_cover_full_range() -> io::Result<()> {
+        assert_eq!(NormFormat::UNORM8.encode(1.0), Some(0xFF));
+        assert_eq!(NormFormat::UNORM8.encode(-3.0), Some(0));
+        assert_eq!(NormFormat::UNORM8.decode(0xFF), 1.0);
+        assert_eq!(NormFormat::SNORM16.decode(0x8000), -1.0);
+        assert_eq!(NormFormat::SNORM16.decode(0x8001), -1.0);
+        assert_eq!(NormFormat::SNORM16.encode(-1.0), Some(0x8001));
+        assert_eq!(NormFormat::SNORM8.with_saturation(false).encode(1.5), None);
+
+        let mut c = Cursor::new(Vec::new());
+        write_norm_f32(&mut c, NormFormat::SNORM16, ByteOrder::Little, 1.0)?;
+        assert_eq!(c.get_ref(), &[0xFF, 0x7F]);
+        let err = write_norm_f64(
+            &mut c,
+            NormFormat::UNORM16.with_saturation(false),
+            ByteOrder::Big,
+            2.0,
+        )
+        .expect_err("Out-of-range value was written");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        c.set_position(0);
+        assert_eq!(
+            read_norm_f64(&mut c, NormFormat::SNORM16, ByteOrder::Little)?,
+            1.0
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,335 @@
+//! A codec for messages sent as length-prefixed frames, such as over TCP or a
+//! [`pipe`].
+//!
+//! Each frame is a length, encoded as an [`IntEncoding`], followed by that
+//! many bytes of payload. Depending on the protocol, the length either counts
+//! only the payload or the header as well.
+//!
+//! [`pipe`]: ../pipe/index.html
+//! [`IntEncoding`]: ../enum.IntEncoding.html
+
+use std::io::{self, Error, Read, Write};
+
+use crate::IntEncoding;
+
+/// The largest frame accepted unless a different limit is set: 16 MiB.
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/**
+ * A bit source of length-prefixed frames.
+ *
+ * [`read_frame`] returns one complete frame per call, however the bytes
+ * arrive. If the underlying bit source returns an error partway through a
+ * frame, such as `WouldBlock` or `TimedOut`, the bytes received so far are
+ * kept and the next call to `read_frame` picks up where the last one left
+ * off. Bytes are never read past the end of the current frame.
+ *
+ * ```
+ * # use extended_io::{frame::{FrameReader, FrameWriter}, pipe::mk_pipe, ByteOrder, IntEncoding};
+ * let (read, write) = mk_pipe();
+ * let mut out = FrameWriter::new(write, IntEncoding::U16(ByteOrder::Big));
+ * out.write_frame(b"hello")?;
+ * out.write_frame(b"")?;
+ * let mut src = FrameReader::new(read, IntEncoding::U16(ByteOrder::Big));
+ * assert_eq!(src.read_frame()?, Some(&b"hello"[..]));
+ * assert_eq!(src.read_frame()?, Some(&b""[..]));
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ *
+ * [`read_frame`]: #method.read_frame
+ */
+#[derive(Debug)]
+pub struct FrameReader<R> {
+    inner: R,
+    prefix: IntEncoding,
+    includes_header: bool,
+    max_frame_size: usize,
+    /// The bytes of the current frame received so far, header included.
+    buf: Vec<u8>,
+    /// The lengths of the header and payload of the current frame, once the
+    /// header has been received.
+    header: Option<(usize, usize)>,
+    /// Whether `buf` holds a frame that has already been returned.
+    complete: bool,
+}
+
+impl<R: Read> FrameReader<R> {
+    /// Read frames from `inner` whose lengths are encoded as `prefix`.
+    pub fn new(inner: R, prefix: IntEncoding) -> Self {
+        Self {
+            inner,
+            prefix,
+            includes_header: false,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            buf: Vec::new(),
+            header: None,
+            complete: false,
+        }
+    }
+
+    /// Treat each length as counting the header as well as the payload.
+    pub fn includes_header(mut self) -> Self {
+        self.includes_header = true;
+        self
+    }
+
+    /// Reject frames whose payload is longer than `max` bytes, instead of the
+    /// default of [`DEFAULT_MAX_FRAME_SIZE`].
+    ///
+    /// [`DEFAULT_MAX_FRAME_SIZE`]: constant.DEFAULT_MAX_FRAME_SIZE.html
+    pub fn max_frame_size(mut self, max: usize) -> Self {
+        self.max_frame_size = max;
+        self
+    }
+
+    /// Get a reference to the underlying bit source.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying bit source.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwrap the reader, returning the underlying bit source. Any partly
+    /// received frame is lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /**
+     * Read the payload of the next frame, or `None` if the bit source is
+     * exhausted at a frame boundary.
+     *
+     * Returns an error of kind `UnexpectedEof` if the bit source is exhausted
+     * partway through a frame, and of kind `InvalidData` if the frame is
+     * larger than the maximum size or its length is malformed. After an
+     * `InvalidData` error, the reader is no longer in step with the frames.
+     */
+    pub fn read_frame(&mut self) -> io::Result<Option<&[u8]>> {
+        if self.complete {
+            self.buf.clear();
+            self.header = None;
+            self.complete = false;
+        }
+        loop {
+            let want = match self.header {
+                Some((header, len)) if self.buf.len() == header + len => break,
+                Some((header, len)) => header + len - self.buf.len(),
+                None => match self.prefix.decode(&self.buf) {
+                    Ok(Some((val, header))) => {
+                        self.header = Some((header, self.payload_len(val, header)?));
+                        continue;
+                    }
+                    Ok(None) if self.prefix == IntEncoding::Varint => 1,
+                    Ok(None) => self.prefix.encoded_len(0) - self.buf.len(),
+                    Err(e) => return Err(self.reset(e)),
+                },
+            };
+            let start = self.buf.len();
+            self.buf.resize(start + want, 0);
+            let result = self.inner.read(&mut self.buf[start..]);
+            self.buf.truncate(start + *result.as_ref().unwrap_or(&0));
+            match result {
+                Ok(0) if start == 0 => return Ok(None),
+                Ok(0) => {
+                    let msg = format!("Frame cut short after {} bytes", start);
+                    let e = Error::new(io::ErrorKind::UnexpectedEof, msg);
+                    return Err(self.reset(e));
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.complete = true;
+        let header = self.header.map_or(0, |(header, _)| header);
+        Ok(Some(&self.buf[header..]))
+    }
+
+    /// Work out the length of the payload from the length in the header.
+    fn payload_len(&mut self, val: u64, header: usize) -> io::Result<usize> {
+        let len = if self.includes_header {
+            match val.checked_sub(header as u64) {
+                Some(len) => len,
+                None => {
+                    let msg = format!("Frame length {} is shorter than its header", val);
+                    let e = Error::new(io::ErrorKind::InvalidData, msg);
+                    return Err(self.reset(e));
+                }
+            }
+        } else {
+            val
+        };
+        if len > self.max_frame_size as u64 {
+            let msg = format!(
+                "Frame of {} bytes exceeds the maximum of {}",
+                len, self.max_frame_size,
+            );
+            return Err(self.reset(Error::new(io::ErrorKind::InvalidData, msg)));
+        }
+        Ok(len as usize)
+    }
+
+    /// Forget the current frame, returning `e`.
+    fn reset(&mut self, e: Error) -> Error {
+        self.buf.clear();
+        self.header = None;
+        e
+    }
+}
+
+/// A bit sink of length-prefixed frames, the counterpart of [`FrameReader`].
+///
+/// [`FrameReader`]: struct.FrameReader.html
+#[derive(Debug)]
+pub struct FrameWriter<W> {
+    inner: W,
+    prefix: IntEncoding,
+    includes_header: bool,
+    max_frame_size: usize,
+    header: Vec<u8>,
+}
+
+impl<W: Write> FrameWriter<W> {
+    /// Write frames to `inner` with their lengths encoded as `prefix`.
+    pub fn new(inner: W, prefix: IntEncoding) -> Self {
+        Self {
+            inner,
+            prefix,
+            includes_header: false,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            header: Vec::with_capacity(10),
+        }
+    }
+
+    /// Make each length count the header as well as the payload.
+    pub fn includes_header(mut self) -> Self {
+        self.includes_header = true;
+        self
+    }
+
+    /// Refuse to write frames whose payload is longer than `max` bytes,
+    /// instead of the default of [`DEFAULT_MAX_FRAME_SIZE`].
+    ///
+    /// [`DEFAULT_MAX_FRAME_SIZE`]: constant.DEFAULT_MAX_FRAME_SIZE.html
+    pub fn max_frame_size(mut self, max: usize) -> Self {
+        self.max_frame_size = max;
+        self
+    }
+
+    /// Get a reference to the underlying bit sink.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying bit sink.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwrap the writer, returning the underlying bit sink.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Write `payload` as a single frame. Returns an error of kind
+    /// `InvalidInput` if it's larger than the maximum frame size or its length
+    /// can't be encoded in the prefix.
+    pub fn write_frame(&mut self, payload: &[u8]) -> io::Result<()> {
+        if payload.len() > self.max_frame_size {
+            let msg = format!(
+                "Frame of {} bytes exceeds the maximum of {}",
+                payload.len(),
+                self.max_frame_size,
+            );
+            return Err(Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+        let mut len = payload.len() as u64;
+        if self.includes_header {
+            // A varint header can grow when its own length is added.
+            let mut header = self.prefix.encoded_len(len);
+            while self.prefix.encoded_len(len + header as u64) != header {
+                header += 1;
+            }
+            len += header as u64;
+        }
+        self.header.clear();
+        self.prefix.write(&mut self.header, len)?;
+        self.inner.write_all(&self.header)?;
+        self.inner.write_all(payload)
+    }
+
+    /// Flush the underlying bit sink.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{test_util::Trickle, ByteOrder};
+
+    #[test]
+    fn reassembles_frames_from_pieces() -> io::Result<()> {
+        let payloads: [&[u8]; 3] = [b"one", &[7; 200], b""];
+        for &prefix in &[
+            IntEncoding::U8,
+            IntEncoding::U16(ByteOrder::Little),
+            IntEncoding::U32(ByteOrder::Big),
+            IntEncoding::Varint,
+        ] {
+            for &includes_header in &[false, true] {
+                let mut out = FrameWriter::new(Vec::new(), prefix);
+                let mut src = FrameReader::new(Trickle::blocking(&[]), prefix);
+                if includes_header {
+                    out = out.includes_header();
+                    src = src.includes_header();
+                }
+                for payload in &payloads {
+                    out.write_frame(payload)?;
+                }
+                let data = out.into_inner();
+                src.get_mut().data = &data;
+                let mut frames = Vec::new();
+                loop {
+                    match src.read_frame() {
+                        Ok(Some(frame)) => frames.push(frame.to_vec()),
+                        Ok(None) => break,
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                assert_eq!(frames, payloads, "{:?} {}", prefix, includes_header);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn enforces_limits() {
+        let prefix = IntEncoding::U32(ByteOrder::Big);
+        let mut src = FrameReader::new(&b"\x00\x00\x10\x00"[..], prefix).max_frame_size(1024);
+        let err = src.read_frame().expect_err("Accepted an oversized frame");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut src = FrameReader::new(&b"\x00\x00\x00\x02"[..], prefix).includes_header();
+        let err = src
+            .read_frame()
+            .expect_err("Accepted a frame shorter than its header");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut src = FrameReader::new(&b"\x00\x00\x00\x05abc"[..], prefix);
+        let err = src.read_frame().expect_err("Accepted a truncated frame");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut out = FrameWriter::new(Vec::new(), IntEncoding::U8);
+        let err = out
+            .write_frame(&[0; 256])
+            .expect_err("Wrote an unencodable length");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(out.into_inner().is_empty());
+    }
+}
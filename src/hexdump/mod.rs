@@ -0,0 +1,506 @@
+//! A [`HexDumpWriter`] which formats the bytes written to it as a hex dump in
+//! the style of `xxd` or `hexdump -C`, and a [`HexDumpReader`] which parses
+//! such dumps back into the bytes they describe.
+//!
+//! [`HexDumpReader`]: struct.HexDumpReader.html
+//! [`HexDumpWriter`]: struct.HexDumpWriter.html
+
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, Error, Read, Write},
+};
+
+/// The layout of a hex dump.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HexDumpStyle {
+    /// The default output of `xxd`:
+    /// `00000000: 4865 6c6c 6f0a                           Hello.`
+    Xxd,
+    /// The output of `hexdump -C`:
+    /// `00000000  48 65 6c 6c 6f 0a                                 |Hello.|`
+    Canonical,
+}
+
+fn printable(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+/**
+ * Writes a hex dump of every byte written to it to the wrapped bit sink. Each
+ * line shows the offset of its first byte, the bytes in hex, and the bytes as
+ * ASCII, with unprintable bytes shown as `.`. Lines are only written once
+ * they're complete, so the final partial line is written by [`finish`] or when
+ * the writer is dropped.
+ *
+ * By default, [`HexDumpStyle::Xxd`] dumps show 16 bytes per line in groups of
+ * two, while [`HexDumpStyle::Canonical`] dumps show 16 bytes per line with an
+ * extra space after every eighth byte, replace runs of identical lines with a
+ * single `*`, and end with the total length.
+ *
+ * [`finish`]: #method.finish
+ * [`HexDumpStyle::Xxd`]: enum.HexDumpStyle.html#variant.Xxd
+ * [`HexDumpStyle::Canonical`]: enum.HexDumpStyle.html#variant.Canonical
+ */
+pub struct HexDumpWriter<W: Write> {
+    inner: Option<W>,
+    style: HexDumpStyle,
+    width: usize,
+    group: usize,
+    squeeze: bool,
+    line: Vec<u8>,
+    offset: u64,
+    prev: Option<Vec<u8>>,
+    squeezing: bool,
+    /// Formatted text that hasn't been written to the bit sink yet.
+    text: Vec<u8>,
+    /// Whether the final line and total length have been formatted.
+    ended: bool,
+}
+
+impl<W: Write> HexDumpWriter<W> {
+    /// Create a writer which writes dumps in the specified style to `inner`.
+    pub fn new(inner: W, style: HexDumpStyle) -> Self {
+        let (group, squeeze) = match style {
+            HexDumpStyle::Xxd => (2, false),
+            HexDumpStyle::Canonical => (1, true),
+        };
+        Self {
+            inner: Some(inner),
+            style,
+            width: 16,
+            group,
+            squeeze,
+            line: Vec::with_capacity(16),
+            offset: 0,
+            prev: None,
+            squeezing: false,
+            text: Vec::new(),
+            ended: false,
+        }
+    }
+
+    /// Show `width` bytes per line.
+    ///
+    /// # Panics
+    /// Panics if `width` is 0.
+    pub fn width(mut self, width: usize) -> Self {
+        assert!(width > 0, "Hex dump lines must hold at least one byte");
+        self.width = width;
+        self
+    }
+
+    /// Separate the bytes of [`HexDumpStyle::Xxd`] dumps into groups of
+    /// `group` bytes. Canonical dumps always show each byte separately.
+    ///
+    /// # Panics
+    /// Panics if `group` is 0.
+    ///
+    /// [`HexDumpStyle::Xxd`]: enum.HexDumpStyle.html#variant.Xxd
+    pub fn group(mut self, group: usize) -> Self {
+        assert!(group > 0, "Hex dump groups must hold at least one byte");
+        if self.style == HexDumpStyle::Xxd {
+            self.group = group;
+        }
+        self
+    }
+
+    /// Replace each run of complete lines that are identical to the line
+    /// before them with a single `*` line.
+    pub fn squeeze(mut self, squeeze: bool) -> Self {
+        self.squeeze = squeeze;
+        self
+    }
+
+    /// Get a reference to the underlying bit sink.
+    pub fn get_ref(&self) -> &W {
+        self.inner.as_ref().unwrap()
+    }
+
+    fn format_line(&self) -> String {
+        let mut out = String::new();
+        match self.style {
+            HexDumpStyle::Xxd => {
+                let _ = write!(out, "{:08x}: ", self.offset);
+                let groups = self.width.div_ceil(self.group);
+                let hex_width = self.width * 2 + groups - 1;
+                let start = out.len();
+                for (idx, byte) in self.line.iter().enumerate() {
+                    if idx > 0 && idx % self.group == 0 {
+                        out.push(' ');
+                    }
+                    let _ = write!(out, "{:02x}", byte);
+                }
+                let pad = hex_width - (out.len() - start);
+                out.extend(std::iter::repeat_n(' ', pad + 2));
+                out.extend(self.line.iter().copied().map(printable));
+            }
+            HexDumpStyle::Canonical => {
+                let _ = write!(out, "{:08x} ", self.offset);
+                for idx in 0..self.width {
+                    if idx % 8 == 0 {
+                        out.push(' ');
+                    }
+                    match self.line.get(idx) {
+                        Some(byte) => {
+                            let _ = write!(out, "{:02x} ", byte);
+                        }
+                        None => out.push_str("   "),
+                    }
+                }
+                out.push_str(" |");
+                out.extend(self.line.iter().copied().map(printable));
+                out.push('|');
+            }
+        }
+        out.push('\n');
+        out
+    }
+
+    /// Format the current line into the text to write, and start a new one.
+    fn end_line(&mut self) {
+        let repeated =
+            self.squeeze && self.line.len() == self.width && self.prev.as_ref() == Some(&self.line);
+        let text = if repeated {
+            if self.squeezing {
+                None
+            } else {
+                Some("*\n".to_string())
+            }
+        } else {
+            Some(self.format_line())
+        };
+        if let Some(text) = text {
+            self.text.extend_from_slice(text.as_bytes());
+        }
+        self.squeezing = repeated;
+        self.offset += self.line.len() as u64;
+        self.prev = Some(std::mem::replace(
+            &mut self.line,
+            Vec::with_capacity(self.width),
+        ));
+    }
+
+    /// Write out the formatted text, keeping whatever the bit sink didn't
+    /// accept if it fails.
+    fn write_text(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        while !self.text.is_empty() {
+            match inner.write(&self.text) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.text.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn write_end(&mut self) -> io::Result<()> {
+        if !self.ended {
+            if !self.line.is_empty() {
+                self.end_line();
+            }
+            if self.style == HexDumpStyle::Canonical && self.offset > 0 {
+                let total = format!("{:08x}\n", self.offset);
+                self.text.extend_from_slice(total.as_bytes());
+            }
+            self.ended = true;
+        }
+        self.write_text()?;
+        self.inner.as_mut().unwrap().flush()
+    }
+
+    /// Write the final partial line and, for canonical dumps, the total length,
+    /// then return the underlying bit sink.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_end()?;
+        Ok(self.inner.take().unwrap())
+    }
+}
+
+impl<W: Write> Write for HexDumpWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Nothing of `buf` is taken if earlier text still can't be written.
+        self.write_text()?;
+        for (idx, &byte) in buf.iter().enumerate() {
+            self.line.push(byte);
+            if self.line.len() == self.width {
+                self.end_line();
+                // The bytes so far are kept, and the error comes up again on
+                // the next write or flush.
+                if self.write_text().is_err() {
+                    return Ok(idx + 1);
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_text()?;
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Drop for HexDumpWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.write_end();
+        }
+    }
+}
+
+/**
+ * Reads the bytes described by a hex dump in either [`HexDumpStyle`] from the
+ * wrapped bit source. The style is detected line by line, the ASCII column is
+ * ignored, and a `*` line repeats the line before it until the offset of the
+ * next line. Malformed lines and offsets that don't match the amount of data
+ * seen so far produce errors of kind `InvalidData`.
+ *
+ * [`HexDumpStyle`]: enum.HexDumpStyle.html
+ */
+pub struct HexDumpReader<R> {
+    src: R,
+    text: String,
+    line_no: usize,
+    offset: u64,
+    line: Vec<u8>,
+    pos: usize,
+    squeezed: bool,
+    repeats: u64,
+    queued: Option<Vec<u8>>,
+}
+
+impl<R: BufRead> HexDumpReader<R> {
+    /// Create a reader which parses the dump in `src`.
+    pub fn new(src: R) -> Self {
+        Self {
+            src,
+            text: String::new(),
+            line_no: 0,
+            offset: 0,
+            line: Vec::new(),
+            pos: 0,
+            squeezed: false,
+            repeats: 0,
+            queued: None,
+        }
+    }
+
+    /// Unwrap the reader, returning the underlying bit source.
+    pub fn into_inner(self) -> R {
+        self.src
+    }
+
+    fn error(&self, msg: &str) -> Error {
+        let msg = format!("Hex dump line {}: {}", self.line_no, msg);
+        Error::new(io::ErrorKind::InvalidData, msg)
+    }
+
+    /// Parse lines until one of them produces data. Returns `false` once the
+    /// dump is exhausted.
+    fn parse_line(&mut self) -> io::Result<bool> {
+        loop {
+            self.text.clear();
+            if self.src.read_line(&mut self.text)? == 0 {
+                if self.squeezed {
+                    return Err(self.error("Dump ends with a repeated line"));
+                }
+                return Ok(false);
+            }
+            self.line_no += 1;
+            let text = self.text.trim_end();
+            if text.is_empty() {
+                continue;
+            }
+            if text == "*" {
+                if self.line.is_empty() {
+                    return Err(self.error("Nothing to repeat"));
+                }
+                self.squeezed = true;
+                continue;
+            }
+            let digits = text
+                .find(|c: char| !c.is_ascii_hexdigit())
+                .unwrap_or(text.len());
+            let offset = u64::from_str_radix(&text[..digits], 16)
+                .map_err(|_| self.error("Expected an offset"))?;
+            let rest = &text[digits..];
+            let hex = match rest.strip_prefix(':') {
+                // xxd: the ASCII column starts after two spaces.
+                Some(rest) => rest.trim_start().split("  ").next().unwrap(),
+                // hexdump -C: the ASCII column is enclosed in bars.
+                None => rest.split('|').next().unwrap(),
+            };
+            let mut bytes = Vec::new();
+            for group in hex.split_whitespace() {
+                if group.len() % 2 != 0 || !group.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    return Err(self.error("Expected hex digits"));
+                }
+                for idx in (0..group.len()).step_by(2) {
+                    bytes.push(u8::from_str_radix(&group[idx..idx + 2], 16).unwrap());
+                }
+            }
+            if self.squeezed {
+                let len = self.line.len() as u64;
+                let skipped = offset.saturating_sub(self.offset);
+                if offset < self.offset || skipped % len != 0 {
+                    return Err(self.error("Offset doesn't follow the repeated line"));
+                }
+                self.squeezed = false;
+                self.repeats = skipped / len;
+                self.offset = offset;
+            }
+            if offset != self.offset {
+                return Err(self.error("Offset doesn't match the data so far"));
+            }
+            if bytes.is_empty() {
+                // The final line of a canonical dump holds the total length.
+                if self.repeats > 0 {
+                    return Ok(true);
+                }
+                continue;
+            }
+            self.offset += bytes.len() as u64;
+            if self.repeats > 0 {
+                self.queued = Some(bytes);
+            } else {
+                self.line = bytes;
+                self.pos = 0;
+            }
+            return Ok(true);
+        }
+    }
+}
+
+impl<R: BufRead> Read for HexDumpReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.pos < self.line.len() {
+                let len = usize::min(buf.len(), self.line.len() - self.pos);
+                buf[..len].copy_from_slice(&self.line[self.pos..self.pos + len]);
+                self.pos += len;
+                return Ok(len);
+            }
+            if self.repeats > 0 {
+                self.repeats -= 1;
+                self.pos = 0;
+            } else if let Some(line) = self.queued.take() {
+                self.line = line;
+                self.pos = 0;
+            } else if !self.parse_line()? {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+/// Parse the hex dump in `text` into the bytes it describes.
+pub fn parse_hex_dump(text: &str) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    HexDumpReader::new(text.as_bytes()).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::Flaky;
+    use std::cell::Cell;
+
+    const DATA: &[u8] =
+        b"Hello, world!\n\x01\x02\x03AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAz";
+
+    const XXD: &str = "\
+00000000: 4865 6c6c 6f2c 2077 6f72 6c64 210a 0102  Hello, world!...
+00000010: 0341 4141 4141 4141 4141 4141 4141 4141  .AAAAAAAAAAAAAAA
+00000020: 4141 4141 4141 4141 4141 4141 4141 4141  AAAAAAAAAAAAAAAA
+00000030: 4141 4141 4141 4141 4141 4141 4141 4141  AAAAAAAAAAAAAAAA
+00000040: 417a                                     Az
+";
+
+    const CANONICAL: &str = "\
+00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 01 02  |Hello, world!...|
+00000010  03 41 41 41 41 41 41 41  41 41 41 41 41 41 41 41  |.AAAAAAAAAAAAAAA|
+00000020  41 41 41 41 41 41 41 41  41 41 41 41 41 41 41 41  |AAAAAAAAAAAAAAAA|
+*
+00000040  41 7a                                             |Az|
+00000042
+";
+
+    fn dump(
+        style: HexDumpStyle,
+        configure: impl Fn(HexDumpWriter<Vec<u8>>) -> HexDumpWriter<Vec<u8>>,
+    ) -> String {
+        let mut writer = configure(HexDumpWriter::new(Vec::new(), style));
+        for chunk in DATA.chunks(7) {
+            writer.write_all(chunk).unwrap();
+        }
+        String::from_utf8(writer.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn writes_xxd_dumps() {
+        assert_eq!(dump(HexDumpStyle::Xxd, |w| w), XXD);
+        assert_eq!(
+            dump(HexDumpStyle::Xxd, |w| w.width(5).group(3))
+                .lines()
+                .nth(2),
+            Some("0000000a: 6c6421 0a01  ld!.."),
+        );
+    }
+
+    #[test]
+    fn writes_canonical_dumps() {
+        assert_eq!(dump(HexDumpStyle::Canonical, |w| w), CANONICAL);
+        let mut writer = HexDumpWriter::new(Vec::new(), HexDumpStyle::Canonical);
+        writer.write_all(&[0; 64]).unwrap();
+        assert_eq!(
+            String::from_utf8(writer.finish().unwrap()).unwrap(),
+            "00000000  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n*\n00000040\n",
+        );
+    }
+
+    #[test]
+    fn parses_dumps() -> io::Result<()> {
+        assert_eq!(parse_hex_dump(XXD)?, DATA);
+        assert_eq!(parse_hex_dump(CANONICAL)?, DATA);
+        assert_eq!(
+            parse_hex_dump("00000000  00 01  |..|\n*\n00000006\n")?,
+            [0, 1, 0, 1, 0, 1]
+        );
+        let err = parse_hex_dump("00000000: 0102\n00000004: 03\n").expect_err("Accepted a gap");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
+
+    #[test]
+    fn survives_sink_errors() -> io::Result<()> {
+        for &(style, expected) in &[
+            (HexDumpStyle::Xxd, XXD),
+            (HexDumpStyle::Canonical, CANONICAL),
+        ] {
+            let failing = Cell::new(true);
+            let mut writer = HexDumpWriter::new(Flaky::new(10, &failing), style);
+            for chunk in DATA.chunks(7) {
+                let mut chunk = chunk;
+                while !chunk.is_empty() {
+                    if let Ok(n) = writer.write(chunk) {
+                        chunk = &chunk[n..];
+                    }
+                }
+            }
+            while writer.flush().is_err() {}
+            failing.set(false);
+            let out = writer.finish()?.data;
+            assert_eq!(String::from_utf8(out).unwrap(), expected);
+        }
+        Ok(())
+    }
+}
@@ -8,7 +8,205 @@ use std::{
     str::FromStr,
 };
 
+pub mod base_encoding;
+pub mod chunked;
+pub mod decoder;
+pub mod fixed;
+pub mod frame;
+pub mod hexdump;
+pub mod lines;
+pub mod newline;
 pub mod pipe;
+pub mod progress;
+pub mod prompt;
+pub mod records;
+pub mod rle;
+pub mod scanner;
+pub mod schema;
+pub mod stuffing;
+pub mod swap;
+pub mod tee;
+pub mod throttle;
+pub mod tlv;
+pub mod trace;
+pub mod transcode;
+
+#[cfg(test)]
+mod test_util;
+
+/// The order in which the bytes of a multi-byte value are stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ByteOrder {
+    /// Most significant byte first, as used by `read_u32` and friends.
+    Big,
+    /// Least significant byte first, as used by `read_u32_le` and friends.
+    Little,
+    /// The platform's own byte order, as used by `read_u32_ne` and friends.
+    Native,
+}
+
+/// The encoding of an unsigned integer field, such as a length prefix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IntEncoding {
+    /// A single byte.
+    U8,
+    /// Two bytes in the specified byte order.
+    U16(ByteOrder),
+    /// Four bytes in the specified byte order.
+    U32(ByteOrder),
+    /// Unsigned LEB128: seven bits per byte, least significant group first,
+    /// with the high bit set on every byte but the last. At most 10 bytes.
+    Varint,
+}
+
+impl IntEncoding {
+    /// The largest value that can be encoded.
+    pub fn max_value(self) -> u64 {
+        match self {
+            Self::U8 => u8::MAX.into(),
+            Self::U16(_) => u16::MAX.into(),
+            Self::U32(_) => u32::MAX.into(),
+            Self::Varint => u64::MAX,
+        }
+    }
+
+    /// The number of bytes in the encoding of `val`.
+    pub fn encoded_len(self, val: u64) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::U16(_) => 2,
+            Self::U32(_) => 4,
+            Self::Varint => (64 - (val | 1).leading_zeros() as usize).div_ceil(7),
+        }
+    }
+
+    /// Read a value in this encoding from the specified bit source.
+    pub fn read(self, src: &mut dyn Read) -> io::Result<u64> {
+        match self {
+            Self::U8 => read_uint(src, 1, ByteOrder::Big),
+            Self::U16(order) => read_uint(src, 2, order),
+            Self::U32(order) => read_uint(src, 4, order),
+            Self::Varint => {
+                let mut bytes = Vec::with_capacity(10);
+                loop {
+                    bytes.push(read_u8(src)?);
+                    if let Some((val, _)) = self.decode(&bytes)? {
+                        return Ok(val);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Write `val` in this encoding to the specified bit sink. Returns an
+    /// error of kind `InvalidInput` if `val` is too large for the encoding.
+    pub fn write(self, out: &mut dyn Write, val: u64) -> io::Result<()> {
+        if val > self.max_value() {
+            let msg = format!("{} doesn't fit in {:?}", val, self);
+            return Err(Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+        match self {
+            Self::U8 => write_uint(out, 1, ByteOrder::Big, val),
+            Self::U16(order) => write_uint(out, 2, order, val),
+            Self::U32(order) => write_uint(out, 4, order, val),
+            Self::Varint => {
+                let mut buf = [0; 10];
+                let len = self.encoded_len(val);
+                for (idx, byte) in buf[..len].iter_mut().enumerate() {
+                    *byte = (val >> (7 * idx)) as u8 & 0x7F;
+                    if idx + 1 < len {
+                        *byte |= 0x80;
+                    }
+                }
+                out.write_all(&buf[..len])
+            }
+        }
+    }
+
+    /// Decode a value from the start of `bytes`, returning it and the number
+    /// of bytes it took up, or `None` if `bytes` ends before the value does.
+    pub(crate) fn decode(self, bytes: &[u8]) -> io::Result<Option<(u64, usize)>> {
+        let width = match self {
+            Self::U8 => 1,
+            Self::U16(_) => 2,
+            Self::U32(_) => 4,
+            Self::Varint => {
+                let mut val = 0;
+                for (idx, &byte) in bytes.iter().enumerate().take(10) {
+                    if idx == 9 && byte > 1 {
+                        let msg = "Varint doesn't fit in a u64";
+                        return Err(Error::new(io::ErrorKind::InvalidData, msg));
+                    }
+                    val |= u64::from(byte & 0x7F) << (7 * idx);
+                    if byte & 0x80 == 0 {
+                        return Ok(Some((val, idx + 1)));
+                    }
+                }
+                return Ok(None);
+            }
+        };
+        match self {
+            _ if bytes.len() < width => Ok(None),
+            Self::U16(order) | Self::U32(order) => {
+                Ok(Some((read_uint(&mut &bytes[..width], width, order)?, width)))
+            }
+            _ => Ok(Some((bytes[0].into(), 1))),
+        }
+    }
+}
+
+/// Read an unsigned integer `width` bytes wide in the specified byte order,
+/// using the primitive reader for that width and order.
+///
+/// # Panics
+/// Panics if `width` is not 1, 2, 4, or 8.
+pub(crate) fn read_uint(src: &mut dyn Read, width: usize, order: ByteOrder) -> io::Result<u64> {
+    use ByteOrder::*;
+    Ok(match (width, order) {
+        (1, Big) => read_u8(src)?.into(),
+        (1, Little) => read_u8_le(src)?.into(),
+        (1, Native) => read_u8_ne(src)?.into(),
+        (2, Big) => read_u16(src)?.into(),
+        (2, Little) => read_u16_le(src)?.into(),
+        (2, Native) => read_u16_ne(src)?.into(),
+        (4, Big) => read_u32(src)?.into(),
+        (4, Little) => read_u32_le(src)?.into(),
+        (4, Native) => read_u32_ne(src)?.into(),
+        (8, Big) => read_u64(src)?,
+        (8, Little) => read_u64_le(src)?,
+        (8, Native) => read_u64_ne(src)?,
+        _ => panic!("Unsupported integer width: {} bytes", width),
+    })
+}
+
+/// Write the low `width` bytes of `val` in the specified byte order, using the
+/// primitive writer for that width and order.
+///
+/// # Panics
+/// Panics if `width` is not 1, 2, 4, or 8.
+pub(crate) fn write_uint(
+    out: &mut dyn Write,
+    width: usize,
+    order: ByteOrder,
+    val: u64,
+) -> io::Result<()> {
+    use ByteOrder::*;
+    match (width, order) {
+        (1, Big) => write_u8(out, val as u8),
+        (1, Little) => write_u8_le(out, val as u8),
+        (1, Native) => write_u8_ne(out, val as u8),
+        (2, Big) => write_u16(out, val as u16),
+        (2, Little) => write_u16_le(out, val as u16),
+        (2, Native) => write_u16_ne(out, val as u16),
+        (4, Big) => write_u32(out, val as u32),
+        (4, Little) => write_u32_le(out, val as u32),
+        (4, Native) => write_u32_ne(out, val as u32),
+        (8, Big) => write_u64(out, val),
+        (8, Little) => write_u64_le(out, val),
+        (8, Native) => write_u64_ne(out, val),
+        _ => panic!("Unsupported integer width: {} bytes", width),
+    }
+}
 
 /**
  * Read a "big-endian" u8 from the specified bit source. Since big-endian and
@@ -289,7 +487,7 @@ pub fn read_f64_ne(src: &mut dyn Read) -> io::Result<f64> {
 /// Read `length` bytes into a new `Vec` from the specified bit source.
 pub fn read_bytes(src: &mut dyn Read, length: u64) -> io::Result<Vec<u8>> {
     let mut handle = src.take(length);
-    let length_usize = usize::try_from(length).unwrap_or(usize::max_value());
+    let length_usize = usize::try_from(length).unwrap_or(usize::MAX);
     let mut buf = Vec::with_capacity(length_usize);
     if let Err(e) = handle.read_to_end(&mut buf) {
         let msg = format!(
@@ -302,6 +500,80 @@ pub fn read_bytes(src: &mut dyn Read, length: u64) -> io::Result<Vec<u8>> {
     }
 }
 
+/**
+ * Read a [netstring], such as `5:hello,`, from the specified bit source and
+ * return its contents. Returns an error of kind `InvalidData` if the netstring
+ * is malformed or its length is more than `max_len`, which is checked before
+ * anything is allocated, and of kind `UnexpectedEof` if the bit source is
+ * exhausted, even before the netstring starts.
+ *
+ * [netstring]: https://cr.yp.to/proto/netstrings.txt
+ */
+pub fn read_netstring(src: &mut dyn Read, max_len: usize) -> io::Result<Vec<u8>> {
+    match try_read_netstring(src, max_len)? {
+        Some(vals) => Ok(vals),
+        None => Err(Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Expected a netstring, but the source is exhausted",
+        )),
+    }
+}
+
+/**
+ * Like [`read_netstring`] but returns `None` instead of an error if the bit
+ * source is exhausted before the first byte of the netstring.
+ *
+ * The length is read one byte at a time, so nothing past the end of the
+ * netstring is consumed.
+ *
+ * [`read_netstring`]: fn.read_netstring.html
+ */
+pub fn try_read_netstring(src: &mut dyn Read, max_len: usize) -> io::Result<Option<Vec<u8>>> {
+    let invalid = |msg: String| Error::new(io::ErrorKind::InvalidData, msg);
+    let mut len: usize = 0;
+    let mut digits = 0;
+    loop {
+        let b = if digits == 0 {
+            let mut first = [0; 1];
+            match src.read(&mut first) {
+                Ok(0) => return Ok(None),
+                Ok(_) => first[0],
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        } else {
+            read_u8(src)?
+        };
+        match b {
+            b':' if digits > 0 => break,
+            b'0'..=b'9' if digits == 1 && len == 0 => {
+                return Err(invalid("Netstring length has a leading zero".to_string()));
+            }
+            b'0'..=b'9' => {
+                let digit = usize::from(b - b'0');
+                len = match len.checked_mul(10).and_then(|len| len.checked_add(digit)) {
+                    Some(len) if len <= max_len => len,
+                    _ => {
+                        let msg = format!("Netstring is longer than the maximum of {}", max_len);
+                        return Err(invalid(msg));
+                    }
+                };
+                digits += 1;
+            }
+            b => {
+                let msg = format!("Unexpected byte 0x{:02x} in netstring length", b);
+                return Err(invalid(msg));
+            }
+        }
+    }
+    let mut vals = vec![0; len];
+    src.read_exact(&mut vals)?;
+    match read_u8(src)? {
+        b',' => Ok(Some(vals)),
+        b => Err(invalid(format!("Expected ',' after netstring, found 0x{:02x}", b))),
+    }
+}
+
 /**
  * Write a "big-endian" u8 to the specified bit sink. Since big-endian and
  * little-endian refer to byte order, not bit order, there is no difference
@@ -533,16 +805,186 @@ pub fn write_byte_slice(out: &mut dyn Write, vals: &[u8]) -> io::Result<()> {
     out.write_all(vals)
 }
 
+/// Write the specified slice of bytes to the specified bit sink as a
+/// netstring.
+pub fn write_netstring(out: &mut dyn Write, vals: &[u8]) -> io::Result<()> {
+    write!(out, "{}:", vals.len())?;
+    out.write_all(vals)?;
+    out.write_all(b",")
+}
+
+/**
+ * Read a single UTF-8 encoded char from the specified bit source. Returns an
+ * error of kind `UnexpectedEof` if the bit source is exhausted, even partway
+ * through the char, and of kind `InvalidData` if the bytes aren't the shortest
+ * UTF-8 encoding of a Unicode scalar value.
+ */
+pub fn read_char(src: &mut dyn Read) -> io::Result<char> {
+    match try_read_char(src)? {
+        Some(c) => Ok(c),
+        None => Err(Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Expected a char, but the source is exhausted",
+        )),
+    }
+}
+
+/**
+ * Like [`read_char`] but returns `None` instead of an error if the bit source
+ * is exhausted before the first byte of the char.
+ *
+ * The bytes are read one at a time and checked as they arrive, so at most one
+ * byte past the end of a malformed sequence is consumed. Use [`chars`] to
+ * decode that byte as the start of the next char instead of losing it.
+ *
+ * [`read_char`]: fn.read_char.html
+ * [`chars`]: fn.chars.html
+ */
+pub fn try_read_char(src: &mut dyn Read) -> io::Result<Option<char>> {
+    read_char_from(src, &mut None)
+}
+
+/// Read a char as `try_read_char` does, starting with the byte in `pending`
+/// if there is one. If a byte that can't continue a char cuts a malformed
+/// char short, it's left in `pending`.
+fn read_char_from(src: &mut dyn Read, pending: &mut Option<u8>) -> io::Result<Option<char>> {
+    let mut first = [0; 1];
+    match pending.take() {
+        Some(b) => first[0] = b,
+        None => loop {
+            match src.read(&mut first) {
+                Ok(0) => return Ok(None),
+                Ok(_) => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        },
+    }
+    let invalid = |msg: String| Error::new(io::ErrorKind::InvalidData, msg);
+    // The number of continuation bytes, the bits of the lead byte, and the
+    // range of the first continuation byte, which excludes overlong
+    // encodings, surrogates, and values beyond U+10FFFF.
+    let (len, mut val, second) = match first[0] {
+        b @ 0x00..=0x7F => return Ok(Some(b.into())),
+        b @ 0xC2..=0xDF => (1, u32::from(b & 0x1F), 0x80..=0xBF),
+        b @ 0xE0 => (2, u32::from(b & 0x0F), 0xA0..=0xBF),
+        b @ 0xED => (2, u32::from(b & 0x0F), 0x80..=0x9F),
+        b @ 0xE1..=0xEF => (2, u32::from(b & 0x0F), 0x80..=0xBF),
+        b @ 0xF0 => (3, u32::from(b & 0x07), 0x90..=0xBF),
+        b @ 0xF4 => (3, u32::from(b & 0x07), 0x80..=0x8F),
+        b @ 0xF1..=0xF3 => (3, u32::from(b & 0x07), 0x80..=0xBF),
+        b => return Err(invalid(format!("0x{:02x} can't start a UTF-8 char", b))),
+    };
+    for idx in 0..len {
+        let b = read_u8(src)?;
+        let range = if idx == 0 { second.clone() } else { 0x80..=0xBF };
+        if !range.contains(&b) {
+            if !(0x80..=0xBF).contains(&b) {
+                *pending = Some(b);
+            }
+            let msg = format!("Invalid byte 0x{:02x} in UTF-8 char", b);
+            return Err(invalid(msg));
+        }
+        val = val << 6 | u32::from(b & 0x3F);
+    }
+    Ok(Some(char::from_u32(val).unwrap()))
+}
+
+/// Write the UTF-8 encoding of the specified char to the specified bit sink.
+pub fn write_char(out: &mut dyn Write, val: char) -> io::Result<()> {
+    out.write_all(val.encode_utf8(&mut [0; 4]).as_bytes())
+}
+
+/**
+ * An iterator over the chars of a UTF-8 encoded bit source, which reads only
+ * as many bytes as it needs for each char. A malformed char produces an error
+ * of kind `InvalidData`, after which the iterator carries on with the next
+ * byte, including one that cut the malformed char short. The iterator ends
+ * after any other error.
+ *
+ * Each char is read a byte at a time, so an unbuffered bit source should be
+ * wrapped in a [`BufReader`] first.
+ *
+ * [`BufReader`]: /std/io/struct.BufReader.html
+ */
+#[derive(Debug)]
+pub struct Chars<R> {
+    src: R,
+    /// A byte that ended a malformed char and starts the next one.
+    pending: Option<u8>,
+    done: bool,
+}
+
+/// Iterate over the UTF-8 encoded chars of the specified bit source.
+pub fn chars<R: Read>(src: R) -> Chars<R> {
+    Chars {
+        src,
+        pending: None,
+        done: false,
+    }
+}
+
+impl<R> Chars<R> {
+    /// Unwrap the iterator, returning the underlying bit source.
+    pub fn into_inner(self) -> R {
+        self.src
+    }
+}
+
+impl<R: Read> Iterator for Chars<R> {
+    type Item = io::Result<char>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match read_char_from(&mut self.src, &mut self.pending) {
+            Ok(c) => {
+                self.done = c.is_none();
+                c.map(Ok)
+            }
+            Err(e) => {
+                self.done = e.kind() != io::ErrorKind::InvalidData;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
 /// Read a line from the specified bit source and convert that string into a T.
+/// Returns an error of kind `UnexpectedEof` if the bit source is exhausted.
 pub fn read_t<T>(src: &mut dyn BufRead) -> io::Result<T>
+where
+    T: FromStr,
+    <T as FromStr>::Err: Into<Box<dyn error::Error + Send + Sync>>,
+{
+    match try_read_t(src)? {
+        Some(x) => Ok(x),
+        None => Err(Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Expected a line, but the source is exhausted",
+        )),
+    }
+}
+
+/**
+ * Like [`read_t`] but returns `None` instead of an error if the bit source is
+ * exhausted, so that every remaining line can be read with
+ * `while let Some(x) = try_read_t(&mut src)? { ... }`.
+ *
+ * [`read_t`]: fn.read_t.html
+ */
+pub fn try_read_t<T>(src: &mut dyn BufRead) -> io::Result<Option<T>>
 where
     T: FromStr,
     <T as FromStr>::Err: Into<Box<dyn error::Error + Send + Sync>>,
 {
     let mut buf = String::new();
-    let _ = src.read_line(&mut buf)?;
+    if src.read_line(&mut buf)? == 0 {
+        return Ok(None);
+    }
     match buf.trim().parse() {
-        Ok(x) => Ok(x),
+        Ok(x) => Ok(Some(x)),
         Err(e) => Err(Error::new(io::ErrorKind::InvalidData, e)),
     }
 }
@@ -550,7 +992,7 @@ where
 /**
  * Like [`read_t`] but specialized to stdin.
  *
- * [`read_t`]: fn.read_t
+ * [`read_t`]: fn.read_t.html
  */
 pub fn read_t_stdin<T>() -> io::Result<T>
 where
@@ -560,6 +1002,19 @@ where
     read_t(&mut io::stdin().lock())
 }
 
+/**
+ * Like [`try_read_t`] but specialized to stdin.
+ *
+ * [`try_read_t`]: fn.try_read_t.html
+ */
+pub fn try_read_t_stdin<T>() -> io::Result<Option<T>>
+where
+    T: FromStr,
+    <T as FromStr>::Err: Into<Box<dyn error::Error + Send + Sync>>,
+{
+    try_read_t(&mut io::stdin().lock())
+}
+
 /// Write the specified string to stdout then read an object of the specified
 /// FromStr type from stdin as a string.
 pub fn prompt<T>(p: &str) -> io::Result<T>
@@ -568,7 +1023,7 @@ where
     <T as FromStr>::Err: Into<Box<dyn error::Error + Send + Sync>>,
 {
     let mut stdout = io::stdout();
-    stdout.write_all(&p.as_bytes()[..])?;
+    stdout.write_all(p.as_bytes())?;
     stdout.flush()?;
     read_t_stdin()
 }
@@ -585,13 +1040,13 @@ mod test {
         let mut c = Cursor::new(Vec::with_capacity(1));
         write_u8(&mut c, v)?;
         let buf = c.into_inner();
-        match buf.get(0) {
+        match buf.first() {
             Some(8u8) => Ok(()),
             Some(x) => {
                 let msg = format!("Expected first byte in buffer to be {}, found {}", v, x);
-                Err(Error::new(ErrorKind::Other, msg))
+                Err(Error::other(msg))
             }
-            None => Err(Error::new(ErrorKind::Other, "Write failed")),
+            None => Err(Error::other("Write failed")),
         }
     }
 
@@ -601,13 +1056,13 @@ mod test {
         let mut c = Cursor::new(Vec::with_capacity(1));
         write_u8_le(&mut c, v)?;
         let buf = c.into_inner();
-        match buf.get(0) {
+        match buf.first() {
             Some(8u8) => Ok(()),
             Some(x) => {
                 let msg = format!("Expected first byte in buffer to be {}, found {}", v, x);
-                Err(Error::new(ErrorKind::Other, msg))
+                Err(Error::other(msg))
             }
-            None => Err(Error::new(ErrorKind::Other, "Write failed")),
+            None => Err(Error::other("Write failed")),
         }
     }
 
@@ -626,14 +1081,14 @@ mod test {
                     "Expected buffer contents to be [{}, {}], found [{}, {}]",
                     high_bits, low_bits, x, y
                 );
-                Err(Error::new(ErrorKind::Other, msg))
+                Err(Error::other(msg))
             }
             slice => {
                 let msg = format!(
                     "Expected buffer contents to be [{}, {}], found {:?}",
                     high_bits, low_bits, slice
                 );
-                Err(Error::new(ErrorKind::Other, msg))
+                Err(Error::other(msg))
             }
         }
     }
@@ -653,15 +1108,127 @@ mod test {
                     "Expected buffer contents to be [{}, {}], found [{}, {}]",
                     low_bits, high_bits, y, x
                 );
-                Err(Error::new(ErrorKind::Other, msg))
+                Err(Error::other(msg))
             }
             slice => {
                 let msg = format!(
                     "Expected buffer contents to be [{}, {}], found {:?}",
                     low_bits, high_bits, slice
                 );
-                Err(Error::new(ErrorKind::Other, msg))
+                Err(Error::other(msg))
             }
         }
     }
+
+    #[test]
+    fn read_t_reports_eof() {
+        let mut src = &b"12\n"[..];
+        assert_eq!(read_t::<u32>(&mut src).unwrap(), 12);
+        assert_eq!(
+            read_t::<u32>(&mut src)
+                .expect_err("Read a value from an exhausted source")
+                .kind(),
+            ErrorKind::UnexpectedEof,
+        );
+    }
+
+    #[test]
+    fn try_read_t_stops_at_eof() -> io::Result<()> {
+        let mut src = &b"1\n2\n\n3"[..];
+        let mut vals = Vec::new();
+        while let Some(x) = try_read_t::<String>(&mut src)? {
+            vals.push(x);
+        }
+        assert_eq!(vals, ["1", "2", "", "3"]);
+        let mut src = &b"x\n"[..];
+        assert_eq!(
+            try_read_t::<i8>(&mut src)
+                .expect_err("Parsed a letter as a number")
+                .kind(),
+            ErrorKind::InvalidData,
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reads_and_writes_chars() -> io::Result<()> {
+        let text = "aé€\u{1F600}";
+        let mut out = Vec::new();
+        for c in text.chars() {
+            write_char(&mut out, c)?;
+        }
+        assert_eq!(out, text.as_bytes());
+        let mut src = &out[..];
+        assert_eq!(read_char(&mut src)?, 'a');
+        assert_eq!(
+            chars(src).collect::<io::Result<String>>()?,
+            "é€\u{1F600}",
+        );
+        let mut src = &b"\xe2\x82"[..];
+        assert_eq!(
+            read_char(&mut src)
+                .expect_err("Read a truncated char")
+                .kind(),
+            ErrorKind::UnexpectedEof,
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_malformed_chars() {
+        // Overlong slash, a surrogate, U+110000, a stray continuation byte,
+        // and a lead byte followed by ASCII.
+        let src = &b"\xc0\xaf|\xed\xa0\x80|\xf4\x90\x80\x80|\x80|\xe2x|"[..];
+        let results = chars(src)
+            .map(|c| c.map_err(|e| e.kind()))
+            .collect::<Vec<_>>();
+        let bad = Err(ErrorKind::InvalidData);
+        assert_eq!(
+            results,
+            [
+                bad,
+                bad,
+                Ok('|'),
+                bad,
+                bad,
+                Ok('|'),
+                bad,
+                bad,
+                bad,
+                Ok('|'),
+                bad,
+                Ok('|'),
+                bad,
+                Ok('x'),
+                Ok('|'),
+            ],
+        );
+    }
+
+    #[test]
+    fn reads_and_writes_netstrings() -> io::Result<()> {
+        let mut out = Vec::new();
+        write_netstring(&mut out, b"hello")?;
+        write_netstring(&mut out, b"")?;
+        assert_eq!(out, b"5:hello,0:,");
+        let mut src = &out[..];
+        assert_eq!(read_netstring(&mut src, 5)?, b"hello");
+        assert_eq!(try_read_netstring(&mut src, 5)?, Some(Vec::new()));
+        assert_eq!(try_read_netstring(&mut src, 5)?, None);
+
+        for &bad in &[
+            &b"6:hello,"[..],
+            b"99999999999999999999999:",
+            b"05:hello,",
+            b":,",
+            b"5:hello;",
+            b"-1:",
+        ] {
+            let err = read_netstring(&mut &bad[..], 5).expect_err("Read a bad netstring");
+            assert_eq!(err.kind(), ErrorKind::InvalidData, "{:?}", bad);
+        }
+        let err = read_netstring(&mut &b"5:hel"[..], 5).expect_err("Read a cut netstring");
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        Ok(())
+    }
 }
@@ -0,0 +1,196 @@
+//! An iterator which parses every line of a [`BufRead`] as a value of the same
+//! type, in the same way as [`read_t`].
+//!
+//! [`BufRead`]: /std/io/trait.BufRead.html
+//! [`read_t`]: ../fn.read_t.html
+
+use std::{
+    error, fmt,
+    io::{self, BufRead, Error},
+    marker::PhantomData,
+    str::FromStr,
+};
+
+/// The reason a line couldn't be parsed, along with its 1-based line number.
+/// [`ParseLines`] wraps this in an `io::Error` of kind `InvalidData`.
+///
+/// [`ParseLines`]: struct.ParseLines.html
+#[derive(Debug)]
+pub struct LineError {
+    line: usize,
+    source: Box<dyn error::Error + Send + Sync>,
+}
+
+impl LineError {
+    /// The 1-based number of the line that couldn't be parsed.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for LineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.source)
+    }
+}
+
+impl error::Error for LineError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/**
+ * An iterator over the lines of a bit source, each trimmed and converted into a
+ * T. A line that can't be converted produces an error whose inner error is a
+ * [`LineError`], after which the iterator moves on to the next line unless
+ * [`stop_at_first_error`] was used. The iterator always ends after an I/O
+ * error.
+ *
+ * [`LineError`]: struct.LineError.html
+ * [`stop_at_first_error`]: #method.stop_at_first_error
+ */
+pub struct ParseLines<R, T> {
+    src: R,
+    buf: String,
+    line: usize,
+    skip_blank: bool,
+    comment: Option<String>,
+    stop_at_first_error: bool,
+    done: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Parse every line of the specified bit source as a T.
+pub fn parse_lines<T, R>(src: R) -> ParseLines<R, T>
+where
+    R: BufRead,
+    T: FromStr,
+    <T as FromStr>::Err: Into<Box<dyn error::Error + Send + Sync>>,
+{
+    ParseLines {
+        src,
+        buf: String::new(),
+        line: 0,
+        skip_blank: false,
+        comment: None,
+        stop_at_first_error: false,
+        done: false,
+        _marker: PhantomData,
+    }
+}
+
+impl<R, T> ParseLines<R, T> {
+    /// Skip lines that contain nothing but whitespace.
+    pub fn skip_blank(mut self) -> Self {
+        self.skip_blank = true;
+        self
+    }
+
+    /// Skip lines whose first non-whitespace characters are `prefix`.
+    pub fn skip_comments(mut self, prefix: impl Into<String>) -> Self {
+        self.comment = Some(prefix.into());
+        self
+    }
+
+    /// End the iteration after the first error of any kind, so that the
+    /// iterator can be collected into an `io::Result<Vec<T>>` without reading
+    /// any further than necessary.
+    pub fn stop_at_first_error(mut self) -> Self {
+        self.stop_at_first_error = true;
+        self
+    }
+
+    /// The number of lines read so far, including skipped lines.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Unwrap the iterator, returning the underlying bit source.
+    pub fn into_inner(self) -> R {
+        self.src
+    }
+}
+
+impl<R, T> Iterator for ParseLines<R, T>
+where
+    R: BufRead,
+    T: FromStr,
+    <T as FromStr>::Err: Into<Box<dyn error::Error + Send + Sync>>,
+{
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            self.buf.clear();
+            match self.src.read_line(&mut self.buf) {
+                Ok(0) => self.done = true,
+                Ok(_) => {
+                    self.line += 1;
+                    let line = self.buf.trim();
+                    let skip = (self.skip_blank && line.is_empty())
+                        || self
+                            .comment
+                            .as_ref()
+                            .is_some_and(|prefix| line.starts_with(prefix.as_str()));
+                    if skip {
+                        continue;
+                    }
+                    return match line.parse() {
+                        Ok(x) => Some(Ok(x)),
+                        Err(e) => {
+                            self.done = self.stop_at_first_error;
+                            let err = LineError {
+                                line: self.line,
+                                source: e.into(),
+                            };
+                            Some(Err(Error::new(io::ErrorKind::InvalidData, err)))
+                        }
+                    };
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn skips_blank_and_comment_lines() {
+        let src = &b"1\n\n  # two\n3\nfour\n5"[..];
+        let vals = parse_lines::<i32, _>(src)
+            .skip_blank()
+            .skip_comments("#")
+            .map(|x| x.map_err(|e| e.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vals,
+            vec![
+                Ok(1),
+                Ok(3),
+                Err("line 5: invalid digit found in string".to_string()),
+                Ok(5),
+            ],
+        );
+    }
+
+    #[test]
+    fn stops_at_first_error() {
+        let mut lines = parse_lines::<u8, _>(&b"1\n\n3\n"[..]).stop_at_first_error();
+        assert_eq!(lines.next().unwrap().unwrap(), 1);
+        let err = lines.next().unwrap().expect_err("Parsed a blank line");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = err.into_inner().unwrap().downcast::<LineError>().unwrap();
+        assert_eq!(err.line(), 2);
+        assert!(lines.next().is_none());
+        assert_eq!(lines.line(), 2);
+    }
+}
@@ -0,0 +1,298 @@
+//! Adapters that translate line endings: [`NormalizingReader`] turns CRLF and
+//! lone CR into LF, and [`CrlfWriter`] turns lone LF into CRLF.
+//!
+//! [`NormalizingReader`]: struct.NormalizingReader.html
+//! [`CrlfWriter`]: struct.CrlfWriter.html
+
+use std::io::{self, Read, Write};
+
+/// A style of line ending.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LineEnding {
+    /// `\n`, as used by Unix.
+    Lf,
+    /// `\r\n`, as used by Windows and many network protocols.
+    CrLf,
+    /// `\r`, as used by classic Mac OS.
+    Cr,
+}
+
+/// The number of line endings of each style seen so far.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct LineEndingCounts {
+    /// The number of lone `\n`s.
+    pub lf: u64,
+    /// The number of `\r\n`s.
+    pub crlf: u64,
+    /// The number of lone `\r`s.
+    pub cr: u64,
+}
+
+impl LineEndingCounts {
+    /// The most common style, if any line endings have been seen. Ties are
+    /// broken in favor of LF, then CRLF.
+    pub fn dominant(&self) -> Option<LineEnding> {
+        let max = self.lf.max(self.crlf).max(self.cr);
+        if max == 0 {
+            None
+        } else if self.lf == max {
+            Some(LineEnding::Lf)
+        } else if self.crlf == max {
+            Some(LineEnding::CrLf)
+        } else {
+            Some(LineEnding::Cr)
+        }
+    }
+
+    /// Count `byte`, given whether the byte before it was `\r`. A `\r` is
+    /// counted once the byte after it is known.
+    fn record(&mut self, byte: u8, after_cr: bool) {
+        match (after_cr, byte) {
+            (true, b'\n') => self.crlf += 1,
+            (true, _) => self.cr += 1,
+            (false, b'\n') => self.lf += 1,
+            (false, _) => {}
+        }
+    }
+}
+
+/// Count the line endings of each style in `bytes`.
+pub fn count_line_endings(bytes: &[u8]) -> LineEndingCounts {
+    let mut counts = LineEndingCounts::default();
+    let mut after_cr = false;
+    for &byte in bytes {
+        counts.record(byte, after_cr);
+        after_cr = byte == b'\r';
+    }
+    if after_cr {
+        counts.cr += 1;
+    }
+    counts
+}
+
+/**
+ * A bit source that translates every CRLF and lone CR from the underlying bit
+ * source into LF, so that text from any platform can be handed to [`read_t`]
+ * or [`parse_lines`]. A CRLF that's split between two reads of the
+ * underlying source is still translated into a single LF.
+ *
+ * The reader also counts the line endings it translates, so that the style
+ * of the original text can be reported with [`counts`].
+ *
+ * ```
+ * # use extended_io::newline::{LineEnding, NormalizingReader};
+ * # use std::io::Read;
+ * let mut src = NormalizingReader::new(&b"one\r\ntwo\rthree\r\n"[..]);
+ * let mut text = String::new();
+ * src.read_to_string(&mut text)?;
+ * assert_eq!(text, "one\ntwo\nthree\n");
+ * assert_eq!(src.counts().dominant(), Some(LineEnding::CrLf));
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ *
+ * [`read_t`]: ../fn.read_t.html
+ * [`parse_lines`]: ../lines/fn.parse_lines.html
+ * [`counts`]: #method.counts
+ */
+#[derive(Debug)]
+pub struct NormalizingReader<R> {
+    inner: R,
+    after_cr: bool,
+    done: bool,
+    counts: LineEndingCounts,
+}
+
+impl<R: Read> NormalizingReader<R> {
+    /// Translate the line endings read from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            after_cr: false,
+            done: false,
+            counts: LineEndingCounts::default(),
+        }
+    }
+
+    /// The line endings translated so far. A CR at the very end of what's
+    /// been read is only counted once the next byte or the end of the source
+    /// is reached.
+    pub fn counts(&self) -> LineEndingCounts {
+        self.counts
+    }
+
+    /// Get a reference to the underlying bit source.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwrap the reader, returning the underlying bit source.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for NormalizingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.done {
+            return Ok(0);
+        }
+        loop {
+            let n = self.inner.read(buf)?;
+            if n == 0 {
+                if self.after_cr {
+                    self.counts.cr += 1;
+                    self.after_cr = false;
+                }
+                self.done = true;
+                return Ok(0);
+            }
+            let mut len = 0;
+            for idx in 0..n {
+                let byte = buf[idx];
+                self.counts.record(byte, self.after_cr);
+                let skip = self.after_cr && byte == b'\n';
+                self.after_cr = byte == b'\r';
+                if !skip {
+                    buf[len] = if byte == b'\r' { b'\n' } else { byte };
+                    len += 1;
+                }
+            }
+            // A read consisting of nothing but the LF of a split CRLF
+            // produces nothing, which mustn't be mistaken for the end.
+            if len > 0 {
+                return Ok(len);
+            }
+        }
+    }
+}
+
+/**
+ * A bit sink that translates every lone LF written to it into CRLF. An LF
+ * that already follows a CR, even one written in an earlier call, is left
+ * alone, so text that's already CRLF passes through unchanged.
+ *
+ * ```
+ * # use extended_io::newline::CrlfWriter;
+ * # use std::io::Write;
+ * let mut out = CrlfWriter::new(Vec::new());
+ * write!(out, "one\ntwo\r\nthree\n")?;
+ * assert_eq!(out.into_inner(), b"one\r\ntwo\r\nthree\r\n");
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ */
+#[derive(Debug)]
+pub struct CrlfWriter<W> {
+    inner: W,
+    after_cr: bool,
+    /// Translated bytes that haven't been written to the bit sink yet.
+    buf: Vec<u8>,
+}
+
+impl<W: Write> CrlfWriter<W> {
+    /// Translate the line endings written to `inner`.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            after_cr: false,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Get a reference to the underlying bit sink.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Unwrap the writer, returning the underlying bit sink. Bytes held back
+    /// after the bit sink failed to accept them are lost.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Write out the translated bytes, keeping whatever the bit sink didn't
+    /// accept if it fails.
+    fn write_buf(&mut self) -> io::Result<()> {
+        while !self.buf.is_empty() {
+            match self.inner.write(&self.buf) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.buf.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for CrlfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Nothing of `buf` is taken if earlier output still can't be written.
+        self.write_buf()?;
+        for &byte in buf {
+            if byte == b'\n' && !self.after_cr {
+                self.buf.push(b'\r');
+            }
+            self.buf.push(byte);
+            self.after_cr = byte == b'\r';
+        }
+        // `buf` is now held by the writer, and any error comes up again on
+        // the next write or flush.
+        let _ = self.write_buf();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_buf()?;
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{Flaky, Trickle};
+    use std::cell::Cell;
+
+    #[test]
+    fn normalizes_split_line_endings() -> io::Result<()> {
+        let text = b"a\r\nb\rc\n\r\r\nd\r";
+        let mut src = NormalizingReader::new(Trickle::new(text));
+        let mut out = Vec::new();
+        src.read_to_end(&mut out)?;
+        assert_eq!(out, b"a\nb\nc\n\n\nd\n");
+        let counts = LineEndingCounts {
+            lf: 1,
+            crlf: 2,
+            cr: 3,
+        };
+        assert_eq!(src.counts(), counts);
+        assert_eq!(count_line_endings(text), counts);
+        assert_eq!(counts.dominant(), Some(LineEnding::Cr));
+
+        let mut src = std::io::BufReader::new(NormalizingReader::new(&b"12\r\n34\r\n"[..]));
+        assert_eq!(crate::read_t::<u32>(&mut src)?, 12);
+        assert_eq!(crate::read_t::<u32>(&mut src)?, 34);
+        Ok(())
+    }
+
+    #[test]
+    fn writes_crlf() -> io::Result<()> {
+        let mut out = CrlfWriter::new(Vec::new());
+        out.write_all(b"a\nb\r")?;
+        out.write_all(b"\nc\r\r")?;
+        out.write_all(b"\n\n")?;
+        assert_eq!(out.into_inner(), b"a\r\nb\r\nc\r\r\n\r\n");
+        assert_eq!(count_line_endings(b"no endings").dominant(), None);
+
+        let failing = Cell::new(true);
+        let mut out = CrlfWriter::new(Flaky::new(3, &failing));
+        for piece in [&b"one\ntwo\r"[..], b"\nthree\n", b"\n"] {
+            while out.write(piece).is_err() {}
+        }
+        while out.flush().is_err() {}
+        assert_eq!(out.into_inner().data, b"one\r\ntwo\r\nthree\r\n\r\n");
+        Ok(())
+    }
+}
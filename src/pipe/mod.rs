@@ -69,12 +69,12 @@ impl Read for Pipe {
         let (bytes_lock, condvar) = &*self.bytes;
         let bytes = bytes_lock.lock().unwrap();
         let condition = |bytes: &mut Vec<_>| {
-            self.has_write_end() && bytes.len() < std::usize::MAX - buf.len()
+            self.has_write_end() && bytes.len() < usize::MAX - buf.len()
         };
         let mut bytes = condvar.wait_while(bytes, condition).unwrap();
         // Either the pipe can no longer receive data or the pipe contains
         // enough data that `buf` can be filled completely.
-        let len = usize::min(std::usize::MAX - buf.len(), bytes.len());
+        let len = usize::min(usize::MAX - buf.len(), bytes.len());
         let bytes = bytes.drain(..len);
         buf.extend(bytes);
         condvar.notify_one();
@@ -85,10 +85,10 @@ impl Read for Pipe {
         let (bytes_lock, condvar) = &*self.bytes;
         let mut bytes = bytes_lock.lock().unwrap();
         let condition = |bytes: &mut Vec<_>| {
-            self.has_write_end() && bytes.len() < std::usize::MAX - buf.len()
+            self.has_write_end() && bytes.len() < usize::MAX - buf.len()
         };
         bytes = condvar.wait_while(bytes, condition).unwrap();
-        let len = usize::min(std::usize::MAX - buf.len(), bytes.len());
+        let len = usize::min(usize::MAX - buf.len(), bytes.len());
         let s = std::str::from_utf8(&bytes[..len])
             .map_err(|e| {
                 condvar.notify_one();
@@ -139,12 +139,12 @@ impl BufRead for Pipe {
         byte: u8,
         buf: &mut Vec<u8>
     ) -> io::Result<usize> {
-        if buf.len() == std::usize::MAX {
+        if buf.len() == usize::MAX {
             return Ok(0);
         }
         let (bytes_lock, condvar) = &*self.bytes;
         let mut bytes = bytes_lock.lock().unwrap();
-        let max_read = std::usize::MAX - buf.len();
+        let max_read = usize::MAX - buf.len();
         let mut next_check = 0;
         let condition = |bytes: &mut Vec<_>| {
             if self.has_write_end() {
@@ -163,7 +163,7 @@ impl BufRead for Pipe {
             }
         };
         bytes = condvar.wait_while(bytes, condition).unwrap();
-        if self.has_write_end() || bytes.len() > 0 && bytes[next_check] == byte {
+        if self.has_write_end() || !bytes.is_empty() && bytes[next_check] == byte {
             // Either `bytes[next_check]` is `byte` or `next_check` is
             // `max_read`.
             let ret = if bytes[next_check] == byte {
@@ -191,7 +191,7 @@ impl BufRead for Pipe {
         let (len, s) = {
             let init = buf.len();
             let mut buf = buf.to_string().into_bytes();
-            let len = self.read_until('\n' as u8, &mut buf)?;
+            let len = self.read_until(b'\n', &mut buf)?;
             buf.drain(..init);
             let s = String::from_utf8(buf).map_err(|e| {
                 io::Error::new(io::ErrorKind::InvalidData, e)
@@ -207,13 +207,13 @@ impl Write for Pipe {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         let (bytes_lock, condvar) = &*self.bytes;
         let mut bytes = bytes_lock.lock().unwrap();
-        let condition = |bytes: &mut Vec<_>| bytes.len() >= std::usize::MAX;
+        let condition = |bytes: &mut Vec<_>| bytes.len() == usize::MAX;
         bytes = condvar.wait_while(bytes, condition).unwrap();
         if !self.has_read_end() {
             condvar.notify_one();
             Err(io::Error::new(io::ErrorKind::BrokenPipe, "Pipe: no readers"))
         } else {
-            let len = usize::min(std::usize::MAX - bytes.len(), buf.len());
+            let len = usize::min(usize::MAX - bytes.len(), buf.len());
             bytes.reserve(len);
             bytes.extend_from_slice(buf);
             condvar.notify_one();
@@ -228,7 +228,7 @@ impl Write for Pipe {
     fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
         let (bytes_lock, condvar) = &*self.bytes;
         let mut bytes = bytes_lock.lock().unwrap();
-        let condition = |bytes: &mut Vec<_>| bytes.len() > std::usize::MAX - buf.len();
+        let condition = |bytes: &mut Vec<_>| bytes.len() > usize::MAX - buf.len();
         bytes = condvar.wait_while(bytes, condition).unwrap();
         if !self.has_read_end() {
             condvar.notify_one();
@@ -388,7 +388,7 @@ mod test {
     #[test]
     fn test_close_write() {
         let (mut a_to_b_read, mut a_to_b_write) = mk_pipe();
-        let _ = write!(a_to_b_write, "Hi").unwrap();
+        write!(a_to_b_write, "Hi").unwrap();
         std::mem::drop(a_to_b_write);
         let mut buf = [0u8; 5];
         assert_eq!(
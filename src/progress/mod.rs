@@ -0,0 +1,442 @@
+//! Adapters that report how far a transfer has got, either to a callback or,
+//! through [`ProgressBar`], as a text progress bar.
+//!
+//! By default a report is made every 64 KiB. Once [`every_bytes`] or
+//! [`every`] is used, reports are made only when one of the chosen thresholds
+//! is reached. A [`ProgressReader`] also reports once when it reaches the end
+//! of its source, and either adapter can be made to report at any time with
+//! `report`.
+//!
+//! [`ProgressBar`]: struct.ProgressBar.html
+//! [`ProgressReader`]: struct.ProgressReader.html
+//! [`every_bytes`]: struct.ProgressReader.html#method.every_bytes
+//! [`every`]: struct.ProgressReader.html#method.every
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+use crate::throttle::{Clock, SystemClock};
+
+const DEFAULT_EVERY_BYTES: u64 = 64 * 1024;
+
+/// A snapshot of a transfer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Progress {
+    /// The number of bytes transferred so far.
+    pub bytes: u64,
+    /// The number of bytes that will have been transferred at the end, if
+    /// it's known.
+    pub total: Option<u64>,
+    /// The time since the transfer started.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// The average number of bytes transferred per second, if any time has
+    /// passed.
+    pub fn throughput(&self) -> Option<f64> {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            Some(self.bytes as f64 / secs)
+        } else {
+            None
+        }
+    }
+
+    /// The fraction of the transfer that's complete, if the total is known.
+    pub fn fraction(&self) -> Option<f64> {
+        match self.total? {
+            0 => Some(1.0),
+            total => Some((self.bytes as f64 / total as f64).min(1.0)),
+        }
+    }
+
+    /// The estimated time until the transfer is complete, if the total is
+    /// known and the throughput can be measured.
+    pub fn eta(&self) -> Option<Duration> {
+        let remaining = self.total?.saturating_sub(self.bytes);
+        if remaining == 0 {
+            return Some(Duration::ZERO);
+        }
+        match self.throughput()? {
+            rate if rate > 0.0 => Some(Duration::from_secs_f64(remaining as f64 / rate)),
+            _ => None,
+        }
+    }
+}
+
+/// The bookkeeping shared by the reader and the writer.
+struct Tracker<'a, C> {
+    bytes: u64,
+    total: Option<u64>,
+    every_bytes: Option<u64>,
+    every: Option<Duration>,
+    next_bytes: u64,
+    start: Duration,
+    last_report: Duration,
+    last_reported: Option<u64>,
+    clock: C,
+    callback: Box<dyn FnMut(&Progress) + 'a>,
+}
+
+impl<'a> Tracker<'a, SystemClock> {
+    fn new(callback: impl FnMut(&Progress) + 'a) -> Self {
+        Self {
+            bytes: 0,
+            total: None,
+            every_bytes: None,
+            every: None,
+            next_bytes: DEFAULT_EVERY_BYTES,
+            start: Duration::ZERO,
+            last_report: Duration::ZERO,
+            last_reported: None,
+            clock: SystemClock::new(),
+            callback: Box::new(callback),
+        }
+    }
+}
+
+impl<'a, C: Clock> Tracker<'a, C> {
+    fn every_bytes(&mut self, n: u64) {
+        assert!(n > 0, "The reporting threshold must be positive");
+        self.every_bytes = Some(n);
+        self.next_bytes = self.bytes + n;
+    }
+
+    fn with_clock<C2: Clock>(self, clock: C2) -> Tracker<'a, C2> {
+        let now = clock.now();
+        Tracker {
+            bytes: self.bytes,
+            total: self.total,
+            every_bytes: self.every_bytes,
+            every: self.every,
+            next_bytes: self.next_bytes,
+            start: now,
+            last_report: now,
+            last_reported: self.last_reported,
+            clock,
+            callback: self.callback,
+        }
+    }
+
+    fn advance(&mut self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        self.bytes += n as u64;
+        let now = self.clock.now();
+        let by_bytes =
+            (self.every_bytes.is_some() || self.every.is_none()) && self.bytes >= self.next_bytes;
+        let by_time = self
+            .every
+            .is_some_and(|every| now.saturating_sub(self.last_report) >= every);
+        if by_bytes || by_time {
+            self.report_at(now);
+        }
+    }
+
+    fn report(&mut self) {
+        let now = self.clock.now();
+        self.report_at(now);
+    }
+
+    fn report_at(&mut self, now: Duration) {
+        let step = self.every_bytes.unwrap_or(DEFAULT_EVERY_BYTES);
+        self.next_bytes = self.bytes - self.bytes % step + step;
+        self.last_report = now;
+        self.last_reported = Some(self.bytes);
+        let progress = Progress {
+            bytes: self.bytes,
+            total: self.total,
+            elapsed: now.saturating_sub(self.start),
+        };
+        (self.callback)(&progress);
+    }
+}
+
+macro_rules! builders {
+    ($name:ident, $inner:ident) => {
+        impl<'a, T, C: Clock> $name<'a, T, C> {
+            /// Set the total number of bytes that the transfer will involve,
+            /// which makes a fraction and an ETA available.
+            pub fn total(mut self, total: u64) -> Self {
+                self.tracker.total = Some(total);
+                self
+            }
+
+            /**
+             * Report whenever the byte count reaches another multiple of `n`.
+             *
+             * # Panics
+             * Panics if `n` is 0.
+             */
+            pub fn every_bytes(mut self, n: u64) -> Self {
+                self.tracker.every_bytes(n);
+                self
+            }
+
+            /// Report whenever `interval` has passed since the last report.
+            /// The time is only checked when bytes are transferred.
+            pub fn every(mut self, interval: Duration) -> Self {
+                self.tracker.every = Some(interval);
+                self
+            }
+
+            /// Measure time with `clock` instead.
+            pub fn clock<C2: Clock>(self, clock: C2) -> $name<'a, T, C2> {
+                $name {
+                    $inner: self.$inner,
+                    tracker: self.tracker.with_clock(clock),
+                }
+            }
+
+            /// The number of bytes transferred so far.
+            pub fn bytes(&self) -> u64 {
+                self.tracker.bytes
+            }
+
+            /// Report the current progress immediately.
+            pub fn report(&mut self) {
+                self.tracker.report();
+            }
+
+            /// Unwrap the adapter, returning the wrapped value.
+            pub fn into_inner(self) -> T {
+                self.$inner
+            }
+        }
+    };
+}
+
+/**
+ * A bit source that reports the number of bytes read from it.
+ *
+ * ```
+ * # use extended_io::{self as eio, progress::ProgressReader};
+ * let mut seen = Vec::new();
+ * let mut src = ProgressReader::new(&[0u8; 10][..], |p| seen.push(p.bytes)).every_bytes(4);
+ * for _ in 0..5 {
+ *     eio::read_u16(&mut src)?;
+ * }
+ * assert!(eio::read_u8(&mut src).is_err());
+ * drop(src);
+ * assert_eq!(seen, [4, 8, 10]);
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ */
+pub struct ProgressReader<'a, R, C = SystemClock> {
+    inner: R,
+    tracker: Tracker<'a, C>,
+}
+
+impl<'a, R: Read> ProgressReader<'a, R> {
+    /// Read from `inner`, passing progress reports to `callback`.
+    pub fn new(inner: R, callback: impl FnMut(&Progress) + 'a) -> Self {
+        Self {
+            inner,
+            tracker: Tracker::new(callback),
+        }
+    }
+}
+
+builders!(ProgressReader, inner);
+
+impl<R: Read, C: Clock> Read for ProgressReader<'_, R, C> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 && !buf.is_empty() && self.tracker.last_reported != Some(self.tracker.bytes) {
+            self.tracker.report();
+        }
+        self.tracker.advance(n);
+        Ok(n)
+    }
+}
+
+/// A bit sink that reports the number of bytes written to it.
+pub struct ProgressWriter<'a, W, C = SystemClock> {
+    inner: W,
+    tracker: Tracker<'a, C>,
+}
+
+impl<'a, W: Write> ProgressWriter<'a, W> {
+    /// Write to `inner`, passing progress reports to `callback`.
+    pub fn new(inner: W, callback: impl FnMut(&Progress) + 'a) -> Self {
+        Self {
+            inner,
+            tracker: Tracker::new(callback),
+        }
+    }
+}
+
+builders!(ProgressWriter, inner);
+
+impl<W: Write, C: Clock> Write for ProgressWriter<'_, W, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.tracker.advance(n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A byte count in binary units, such as `1.5 MiB`.
+struct Bytes(f64);
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+        if self.0 < 1024.0 {
+            return write!(f, "{} B", self.0.round());
+        }
+        let mut val = self.0 / 1024.0;
+        let mut unit = 0;
+        while val >= 1024.0 && unit + 1 < UNITS.len() {
+            val /= 1024.0;
+            unit += 1;
+        }
+        write!(f, "{:.1} {}", val, UNITS[unit])
+    }
+}
+
+/**
+ * A text progress bar, redrawn in place with a carriage return each time it's
+ * given a report. With a known total it looks like
+ *
+ * ```text
+ * [=========>          ]  50% 5.0 MiB/10.0 MiB 1.0 MiB/s ETA 0:05
+ * ```
+ *
+ * and without one it shows just the byte count and throughput.
+ *
+ * ```
+ * # use extended_io::progress::{ProgressBar, ProgressWriter};
+ * # use std::io::Write;
+ * let mut bar = ProgressBar::new(std::io::stderr());
+ * let mut out = ProgressWriter::new(Vec::new(), move |p| bar.draw(p)).total(1 << 20);
+ * out.write_all(&[0; 1 << 20])?;
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ */
+#[derive(Debug)]
+pub struct ProgressBar<W> {
+    out: W,
+    width: usize,
+}
+
+impl<W: Write> ProgressBar<W> {
+    /// Draw the bar on `out`.
+    pub fn new(out: W) -> Self {
+        Self { out, width: 20 }
+    }
+
+    /// Make the bar itself `width` characters wide, not counting the
+    /// brackets.
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Redraw the bar to show `progress`. Errors writing the bar are ignored
+    /// so that they can't interrupt the transfer being reported on.
+    pub fn draw(&mut self, progress: &Progress) {
+        let _ = self.try_draw(progress);
+    }
+
+    fn try_draw(&mut self, progress: &Progress) -> io::Result<()> {
+        let mut line = String::from("\r");
+        if let Some(fraction) = progress.fraction() {
+            let filled = (fraction * self.width as f64) as usize;
+            line.push('[');
+            line.push_str(&"=".repeat(filled));
+            if filled < self.width {
+                line.push('>');
+                line.push_str(&" ".repeat(self.width - filled - 1));
+            }
+            line.push_str(&format!("] {:>3}% ", (fraction * 100.0) as u32));
+        }
+        line.push_str(&Bytes(progress.bytes as f64).to_string());
+        if let Some(total) = progress.total {
+            line.push_str(&format!("/{}", Bytes(total as f64)));
+        }
+        if let Some(rate) = progress.throughput() {
+            line.push_str(&format!(" {}/s", Bytes(rate)));
+        }
+        if let Some(eta) = progress.eta() {
+            let secs = eta.as_secs();
+            if secs >= 3600 {
+                let eta = format!(
+                    " ETA {}:{:02}:{:02}",
+                    secs / 3600,
+                    secs / 60 % 60,
+                    secs % 60
+                );
+                line.push_str(&eta);
+            } else {
+                line.push_str(&format!(" ETA {}:{:02}", secs / 60, secs % 60));
+            }
+        }
+        // Blank out anything left over from a longer previous line.
+        line.push_str("  ");
+        self.out.write_all(line.as_bytes())?;
+        self.out.flush()
+    }
+
+    /// Unwrap the bar, returning the bit sink it draws on.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::FakeClock;
+    use std::cell::RefCell;
+
+    #[test]
+    fn reports_by_bytes_and_by_time() -> io::Result<()> {
+        let clock = FakeClock::default();
+        let reports = RefCell::new(Vec::new());
+        let mut out = ProgressWriter::new(Vec::new(), |p| reports.borrow_mut().push(*p))
+            .total(100)
+            .every(Duration::from_secs(1))
+            .clock(&clock);
+        out.write_all(&[0; 10])?;
+        clock.sleep(Duration::from_secs(1));
+        out.write_all(&[0; 15])?;
+        out.write_all(&[0; 25])?;
+        drop(out);
+        let reports = reports.into_inner();
+        assert_eq!(reports.len(), 1);
+        let report = reports[0];
+        assert_eq!(report.bytes, 25);
+        assert_eq!(report.throughput(), Some(25.0));
+        assert_eq!(report.fraction(), Some(0.25));
+        assert_eq!(report.eta(), Some(Duration::from_secs(3)));
+        Ok(())
+    }
+
+    #[test]
+    fn draws_a_bar() -> io::Result<()> {
+        let clock = FakeClock::default();
+        let mut bar = ProgressBar::new(Vec::new()).width(10);
+        let mut src = ProgressReader::new(&[0; 3 << 20][..], |p| bar.draw(p))
+            .total(4 << 20)
+            .every_bytes(3 << 20)
+            .clock(&clock);
+        clock.sleep(Duration::from_secs(2));
+        io::copy(&mut src, &mut io::sink())?;
+        drop(src);
+        assert_eq!(
+            String::from_utf8(bar.into_inner()).unwrap(),
+            "\r[=======>  ]  75% 3.0 MiB/4.0 MiB 1.5 MiB/s ETA 0:00  ",
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+//! Interactive prompts which keep asking until they get acceptable input.
+//!
+//! Unlike [`prompt`], which gives up on the first bad input, a [`Prompt`] can
+//! validate its input, retry a limited or unlimited number of times, and fall
+//! back to a default value when the user enters nothing. Every prompt can be
+//! run against any pair of input and output handles, so scripted input works
+//! just as well as a terminal.
+//!
+//! [`select`] and [`multi_select`] ask the user to choose from a numbered
+//! list and return the indices of the chosen items, while [`select_item`] and
+//! [`multi_select_items`] return the items themselves. On Linux,
+//! [`prompt_password`] reads a line without echoing it.
+//!
+//! [`prompt`]: ../fn.prompt.html
+//! [`Prompt`]: struct.Prompt.html
+//! [`prompt_password`]: fn.prompt_password.html
+//! [`select`]: fn.select.html
+//! [`multi_select`]: fn.multi_select.html
+//! [`select_item`]: fn.select_item.html
+//! [`multi_select_items`]: fn.multi_select_items.html
+
+use std::{
+    error,
+    io::{self, BufRead, Error, Write},
+    str::FromStr,
+};
+
+#[cfg(target_os = "linux")]
+mod password;
+mod select;
+
+#[cfg(target_os = "linux")]
+pub use password::{prompt_password, prompt_password_on};
+pub use select::{
+    multi_select, multi_select_items, multi_select_items_on, multi_select_on, select, select_item,
+    select_item_on, select_on,
+};
+
+type Parser<'a, T> = Box<dyn Fn(&str) -> Result<T, String> + 'a>;
+type Validator<'a, T> = Box<dyn Fn(&T) -> Result<(), String> + 'a>;
+type Fallback<'a, T> = Box<dyn Fn() -> T + 'a>;
+
+/**
+ * A reusable prompt for a value of type T.
+ *
+ * When the input can't be parsed or is rejected by the validator, the reason
+ * is written on its own line and the user is asked again, using the retry
+ * message if one was set. Once the retry limit is exhausted, the reason is
+ * returned as an error of kind `InvalidData`. If the input is closed before a
+ * value is accepted, an error of kind `UnexpectedEof` is returned.
+ *
+ * ```no_run
+ * # use extended_io::prompt::Prompt;
+ * let age: u32 = Prompt::new("Age: ")
+ *     .validate(|age| if *age < 150 { Ok(()) } else { Err("Too old".into()) })
+ *     .retries(3)
+ *     .interact()?;
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ */
+pub struct Prompt<'a, T> {
+    message: String,
+    retry_message: Option<String>,
+    default: Option<Fallback<'a, T>>,
+    retries: Option<usize>,
+    parser: Parser<'a, T>,
+    validator: Option<Validator<'a, T>>,
+}
+
+impl<'a, T> Prompt<'a, T>
+where
+    T: FromStr,
+    <T as FromStr>::Err: Into<Box<dyn error::Error + Send + Sync>>,
+{
+    /// Create a prompt which displays `message` and parses the trimmed input
+    /// line as a T.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self::with_parser(message, |s| {
+            s.parse().map_err(|e: T::Err| e.into().to_string())
+        })
+    }
+}
+
+impl<'a> Prompt<'a, bool> {
+    /// Create a prompt which displays `message` and accepts "y", "yes", "n",
+    /// or "no" in any case.
+    pub fn confirm(message: impl Into<String>) -> Self {
+        Self::with_parser(message, |s| match s.to_ascii_lowercase().as_str() {
+            "y" | "yes" => Ok(true),
+            "n" | "no" => Ok(false),
+            _ => Err(format!("Expected yes or no, found {:?}", s)),
+        })
+    }
+}
+
+impl<'a, T> Prompt<'a, T> {
+    /// Create a prompt which displays `message` and converts the trimmed input
+    /// line into a T with `parser`, which explains why it rejected any input
+    /// it couldn't convert.
+    pub fn with_parser<F>(message: impl Into<String>, parser: F) -> Self
+    where
+        F: Fn(&str) -> Result<T, String> + 'a,
+    {
+        Self {
+            message: message.into(),
+            retry_message: None,
+            default: None,
+            retries: None,
+            parser: Box::new(parser),
+            validator: None,
+        }
+    }
+
+    /// Accept a parsed value only if `validator` returns `Ok`. The `Err`
+    /// value explains why the input was rejected.
+    pub fn validate<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&T) -> Result<(), String> + 'a,
+    {
+        self.validator = Some(Box::new(validator));
+        self
+    }
+
+    /// Return `default` when the user enters an empty line. The default is
+    /// not checked by the validator.
+    pub fn default(mut self, default: T) -> Self
+    where
+        T: Clone + 'a,
+    {
+        self.default = Some(Box::new(move || default.clone()));
+        self
+    }
+
+    /// Ask again at most `retries` times after the first rejected input. By
+    /// default, the prompt asks until it gets acceptable input.
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retries = Some(retries);
+        self
+    }
+
+    /// Display `message` instead of the original message when asking again.
+    pub fn retry_message(mut self, message: impl Into<String>) -> Self {
+        self.retry_message = Some(message.into());
+        self
+    }
+
+    /// Ask on stdout and read the answer from stdin.
+    pub fn interact(&self) -> io::Result<T> {
+        self.interact_on(&mut io::stdin().lock(), &mut io::stdout())
+    }
+
+    /// Ask on `output` and read the answer from `input`.
+    pub fn interact_on(&self, input: &mut dyn BufRead, output: &mut dyn Write) -> io::Result<T> {
+        let mut message = &self.message;
+        let mut attempts = 0;
+        loop {
+            output.write_all(message.as_bytes())?;
+            output.flush()?;
+            let mut buf = String::new();
+            if input.read_line(&mut buf)? == 0 {
+                return Err(Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "Prompt: input closed before a value was accepted",
+                ));
+            }
+            let line = buf.trim();
+            if let (true, Some(default)) = (line.is_empty(), &self.default) {
+                return Ok(default());
+            }
+            let reason = match self.check(line) {
+                Ok(x) => return Ok(x),
+                Err(reason) => reason,
+            };
+            if self.retries.is_some_and(|retries| attempts >= retries) {
+                return Err(Error::new(io::ErrorKind::InvalidData, reason));
+            }
+            attempts += 1;
+            writeln!(output, "{}", reason)?;
+            message = self.retry_message.as_ref().unwrap_or(&self.message);
+        }
+    }
+
+    fn check(&self, line: &str) -> Result<T, String> {
+        let val = (self.parser)(line)?;
+        if let Some(validator) = &self.validator {
+            validator(&val)?;
+        }
+        Ok(val)
+    }
+}
+
+/// Write the specified string to stdout then read "yes" or "no" from stdin,
+/// asking again until one of them is entered.
+pub fn confirm(p: &str) -> io::Result<bool> {
+    Prompt::confirm(p).interact()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn retries_until_valid() -> io::Result<()> {
+        let mut input = &b"abc\n12\n7\n"[..];
+        let mut output = Vec::new();
+        let val = Prompt::<u8>::new("Pick: ")
+            .validate(|x| {
+                if *x < 10 {
+                    Ok(())
+                } else {
+                    Err("Too big".into())
+                }
+            })
+            .retry_message("Again: ")
+            .interact_on(&mut input, &mut output)?;
+        assert_eq!(val, 7);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "Pick: invalid digit found in string\nAgain: Too big\nAgain: ",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn gives_up_after_retries() {
+        let mut input = &b"x\ny\nz\n"[..];
+        let err = Prompt::<i32>::new("> ")
+            .retries(1)
+            .interact_on(&mut input, &mut io::sink())
+            .expect_err("Accepted invalid input");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(input, b"z\n");
+
+        let err = Prompt::<i32>::new("> ")
+            .interact_on(&mut &b"x\n"[..], &mut io::sink())
+            .expect_err("Accepted closed input");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn confirm_uses_default_on_empty_input() -> io::Result<()> {
+        let prompt = Prompt::confirm("Continue? [Y/n] ").default(true);
+        assert!(prompt.interact_on(&mut &b"\n"[..], &mut io::sink())?);
+        assert!(!prompt.interact_on(&mut &b"maybe\nNO\n"[..], &mut io::sink())?);
+        Ok(())
+    }
+
+    #[test]
+    fn interacts_without_clone() -> io::Result<()> {
+        #[derive(Debug, PartialEq)]
+        struct Name(String);
+
+        let prompt = Prompt::with_parser("Name: ", |s| Ok(Name(s.to_string())));
+        let name = prompt.interact_on(&mut &b"Ada\n"[..], &mut io::sink())?;
+        assert_eq!(name, Name("Ada".to_string()));
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+//! Prompts whose answers aren't echoed to the terminal.
+
+use std::{
+    io::{self, Error, Read, Write},
+    mem::MaybeUninit,
+    os::unix::io::{AsRawFd, RawFd},
+};
+
+/// Turns off echo on a terminal until it's dropped, at which point the
+/// terminal's original settings are restored. Since the settings are restored
+/// in `drop`, they're also restored when the thread unwinds from a panic.
+struct EchoGuard {
+    fd: RawFd,
+    original: libc::termios,
+}
+
+impl EchoGuard {
+    /// Turn off echo on `fd`. Returns `None` if `fd` isn't a terminal.
+    fn new(fd: RawFd) -> io::Result<Option<Self>> {
+        // SAFETY: `isatty` only inspects the descriptor.
+        if unsafe { libc::isatty(fd) } == 0 {
+            return Ok(None);
+        }
+        let mut original = MaybeUninit::uninit();
+        // SAFETY: `original` is valid for writes and is only read after
+        // `tcgetattr` reports that it initialized it.
+        let original = unsafe {
+            if libc::tcgetattr(fd, original.as_mut_ptr()) != 0 {
+                return Err(Error::last_os_error());
+            }
+            original.assume_init()
+        };
+        let mut silent = original;
+        // Still echo the newline so that whatever is written next starts on a
+        // fresh line.
+        silent.c_lflag &= !libc::ECHO;
+        silent.c_lflag |= libc::ECHONL;
+        // SAFETY: `silent` is a fully initialized `termios`.
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &silent) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(Some(Self { fd, original }))
+    }
+}
+
+impl Drop for EchoGuard {
+    fn drop(&mut self) {
+        // SAFETY: `original` is the fully initialized `termios` that
+        // `tcgetattr` returned. There's nothing useful to do on failure.
+        unsafe {
+            libc::tcsetattr(self.fd, libc::TCSANOW, &self.original);
+        }
+    }
+}
+
+/**
+ * Write the specified string to `output` then read a line from `input`
+ * without echoing it, returning the line without its terminator. Echo is
+ * turned off only while the line is being read, and only if `input` is a
+ * terminal; any other input is read as-is, so piped secrets still work.
+ * `input` is read one byte at a time so that nothing past the end of the line
+ * is taken from it. Returns an error of kind `UnexpectedEof` if `input` is
+ * already exhausted or `InvalidData` if the line isn't valid UTF-8.
+ */
+pub fn prompt_password_on<R>(input: &mut R, output: &mut dyn Write, p: &str) -> io::Result<String>
+where
+    R: Read + AsRawFd,
+{
+    let guard = EchoGuard::new(input.as_raw_fd())?;
+    output.write_all(p.as_bytes())?;
+    output.flush()?;
+    let mut buf = Vec::new();
+    let mut byte = [0; 1];
+    loop {
+        match input.read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) if byte[0] == b'\n' => {
+                buf.push(byte[0]);
+                break;
+            }
+            Ok(_) => buf.push(byte[0]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    drop(guard);
+    if buf.is_empty() {
+        return Err(Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Password prompt: input closed",
+        ));
+    }
+    if buf.ends_with(b"\n") {
+        buf.pop();
+        if buf.ends_with(b"\r") {
+            buf.pop();
+        }
+    }
+    String::from_utf8(buf).map_err(|e| Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Write the specified string to stdout then read a line from stdin without
+/// echoing it. See [`prompt_password_on`] for details.
+///
+/// [`prompt_password_on`]: fn.prompt_password_on.html
+pub fn prompt_password(p: &str) -> io::Result<String> {
+    prompt_password_on(&mut io::stdin().lock(), &mut io::stdout(), p)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::{
+        ffi::CStr,
+        fs::{File, OpenOptions},
+        os::unix::{fs::OpenOptionsExt, io::FromRawFd, net::UnixStream},
+        panic, thread,
+    };
+
+    /// Open a pseudo-terminal, returning its master and slave ends.
+    fn open_pty() -> io::Result<(File, File)> {
+        // SAFETY: Each call is checked for failure before its result is used,
+        // and `posix_openpt` returns a fresh descriptor that `File` takes
+        // ownership of.
+        let (master, path) = unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            if fd < 0 {
+                return Err(Error::last_os_error());
+            }
+            let master = File::from_raw_fd(fd);
+            if libc::grantpt(fd) != 0 || libc::unlockpt(fd) != 0 {
+                return Err(Error::last_os_error());
+            }
+            let mut path = [0; 64];
+            if libc::ptsname_r(fd, path.as_mut_ptr(), path.len()) != 0 {
+                return Err(Error::last_os_error());
+            }
+            let path = CStr::from_ptr(path.as_ptr()).to_str().unwrap().to_string();
+            (master, path)
+        };
+        let slave = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(path)?;
+        Ok((master, slave))
+    }
+
+    fn echo_enabled(fd: RawFd) -> bool {
+        let mut attrs = MaybeUninit::uninit();
+        // SAFETY: `attrs` is only read after `tcgetattr` initialized it.
+        unsafe {
+            assert_eq!(libc::tcgetattr(fd, attrs.as_mut_ptr()), 0);
+            attrs.assume_init().c_lflag & libc::ECHO != 0
+        }
+    }
+
+    #[test]
+    fn hides_input_on_a_terminal() -> io::Result<()> {
+        let (mut master, slave) = open_pty()?;
+        let fd = slave.as_raw_fd();
+        assert!(echo_enabled(fd));
+        let mut output = slave.try_clone()?;
+        let reader = thread::spawn(move || {
+            let mut input = slave;
+            prompt_password_on(&mut input, &mut output, "Password: ")
+        });
+        // Echo is off by the time the prompt shows up.
+        let mut buf = [0; 10];
+        master.read_exact(&mut buf)?;
+        assert_eq!(&buf, b"Password: ");
+        master.write_all(b"hunter2\n")?;
+        assert_eq!(reader.join().unwrap()?, "hunter2");
+        let mut buf = [0; 2];
+        master.read_exact(&mut buf)?;
+        assert_eq!(&buf, b"\r\n");
+        Ok(())
+    }
+
+    #[test]
+    fn restores_echo_after_panic() -> io::Result<()> {
+        let (_master, slave) = open_pty()?;
+        let fd = slave.as_raw_fd();
+        let result = panic::catch_unwind(|| {
+            let _guard = EchoGuard::new(fd).unwrap().unwrap();
+            assert!(!echo_enabled(fd));
+            panic!("Interrupted while reading a password");
+        });
+        assert!(result.is_err());
+        assert!(echo_enabled(fd));
+        Ok(())
+    }
+
+    #[test]
+    fn reads_plainly_when_not_a_terminal() -> io::Result<()> {
+        let (mut input, mut write) = UnixStream::pair()?;
+        write.write_all(b"s3cret \r\nleft over")?;
+        assert_eq!(
+            prompt_password_on(&mut input, &mut io::sink(), "")?,
+            "s3cret "
+        );
+        drop(write);
+        let mut rest = String::new();
+        input.read_to_string(&mut rest)?;
+        assert_eq!(rest, "left over");
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+//! Prompts which ask the user to choose from a numbered list.
+
+use std::{
+    fmt::Display,
+    io::{self, BufRead, Error, Write},
+};
+
+use super::Prompt;
+
+/// Write `items` as a numbered list, starting from 1, followed by `p`.
+fn menu<T: Display>(p: &str, items: &[T]) -> String {
+    let width = items.len().to_string().len();
+    let mut menu = String::new();
+    for (idx, item) in items.iter().enumerate() {
+        menu.push_str(&format!("{:>width$}) {}\n", idx + 1, item, width = width));
+    }
+    menu.push_str(p);
+    menu
+}
+
+/// Find the single item chosen by `choice`, which is either a 1-based index
+/// or a case-insensitive prefix of exactly one item. An item whose name
+/// matches `choice` exactly is chosen even if `choice` is a prefix of others.
+fn choose(choice: &str, names: &[String]) -> Result<usize, String> {
+    if let Ok(n) = choice.parse() {
+        return choose_number(n, names.len());
+    }
+    let lower = choice.to_lowercase();
+    if let Some(idx) = names.iter().position(|name| name.to_lowercase() == lower) {
+        return Ok(idx);
+    }
+    let matches = names
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| name.to_lowercase().starts_with(&lower))
+        .collect::<Vec<_>>();
+    match &matches[..] {
+        [] => Err(format!("No item starts with {:?}", choice)),
+        [(idx, _)] => Ok(*idx),
+        _ => {
+            let names = matches
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .collect::<Vec<_>>();
+            Err(format!(
+                "{:?} could be any of: {}",
+                choice,
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+/// Convert a 1-based index into a 0-based one.
+fn choose_number(n: usize, len: usize) -> Result<usize, String> {
+    if (1..=len).contains(&n) {
+        Ok(n - 1)
+    } else {
+        Err(format!("{} is not between 1 and {}", n, len))
+    }
+}
+
+/// Parse a comma-separated list of choices and inclusive ranges of 1-based
+/// indices such as "1,3-5", returning the chosen indices in ascending order
+/// without duplicates.
+fn choose_many(choices: &str, names: &[String]) -> Result<Vec<usize>, String> {
+    let mut chosen = Vec::new();
+    for choice in choices.split(',').map(str::trim) {
+        let range = choice.split_once('-').and_then(|(start, end)| {
+            let start = start.trim().parse::<usize>().ok()?;
+            let end = end.trim().parse::<usize>().ok()?;
+            Some((start, end))
+        });
+        match range {
+            Some((start, end)) => {
+                let start = choose_number(start, names.len())?;
+                let end = choose_number(end, names.len())?;
+                if start > end {
+                    return Err(format!("{:?} is an empty range", choice));
+                }
+                chosen.extend(start..=end);
+            }
+            None if choice.is_empty() => {
+                return Err("Expected an item between each pair of commas".to_string());
+            }
+            None => chosen.push(choose(choice, names)?),
+        }
+    }
+    chosen.sort_unstable();
+    chosen.dedup();
+    Ok(chosen)
+}
+
+fn no_items() -> Error {
+    Error::new(io::ErrorKind::InvalidInput, "Nothing to choose from")
+}
+
+/**
+ * Write `items` as a numbered list followed by `p` to `output`, then read the
+ * user's choice from `input` and return its index in `items`. The user can
+ * enter either the item's number or a case-insensitive prefix of its name,
+ * and is asked again if the choice is out of range or ambiguous. Returns an
+ * error of kind `InvalidInput` if `items` is empty.
+ */
+pub fn select_on<T: Display>(
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    p: &str,
+    items: &[T],
+) -> io::Result<usize> {
+    if items.is_empty() {
+        return Err(no_items());
+    }
+    let names = items.iter().map(T::to_string).collect::<Vec<_>>();
+    let prompt = Prompt::with_parser(menu(p, items), |s| choose(s, &names)).retry_message(p);
+    prompt.interact_on(input, output)
+}
+
+/// Like [`select_on`] but specialized to stdin and stdout.
+///
+/// [`select_on`]: fn.select_on.html
+pub fn select<T: Display>(p: &str, items: &[T]) -> io::Result<usize> {
+    select_on(&mut io::stdin().lock(), &mut io::stdout(), p, items)
+}
+
+/// Like [`select_on`] but returns the chosen item instead of its index.
+///
+/// [`select_on`]: fn.select_on.html
+pub fn select_item_on<'a, T: Display>(
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    p: &str,
+    items: &'a [T],
+) -> io::Result<&'a T> {
+    Ok(&items[select_on(input, output, p, items)?])
+}
+
+/// Like [`select_item_on`] but specialized to stdin and stdout.
+///
+/// [`select_item_on`]: fn.select_item_on.html
+pub fn select_item<'a, T: Display>(p: &str, items: &'a [T]) -> io::Result<&'a T> {
+    select_item_on(&mut io::stdin().lock(), &mut io::stdout(), p, items)
+}
+
+/**
+ * Like [`select_on`] but accepts any number of comma-separated choices, each
+ * of which is either a number, a prefix, or an inclusive range of numbers
+ * such as "3-5". Returns the chosen indices in ascending order without
+ * duplicates.
+ *
+ * [`select_on`]: fn.select_on.html
+ */
+pub fn multi_select_on<T: Display>(
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    p: &str,
+    items: &[T],
+) -> io::Result<Vec<usize>> {
+    if items.is_empty() {
+        return Err(no_items());
+    }
+    let names = items.iter().map(T::to_string).collect::<Vec<_>>();
+    let prompt = Prompt::with_parser(menu(p, items), |s| choose_many(s, &names)).retry_message(p);
+    prompt.interact_on(input, output)
+}
+
+/// Like [`multi_select_on`] but specialized to stdin and stdout.
+///
+/// [`multi_select_on`]: fn.multi_select_on.html
+pub fn multi_select<T: Display>(p: &str, items: &[T]) -> io::Result<Vec<usize>> {
+    multi_select_on(&mut io::stdin().lock(), &mut io::stdout(), p, items)
+}
+
+/// Like [`multi_select_on`] but returns the chosen items instead of their
+/// indices.
+///
+/// [`multi_select_on`]: fn.multi_select_on.html
+pub fn multi_select_items_on<'a, T: Display>(
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    p: &str,
+    items: &'a [T],
+) -> io::Result<Vec<&'a T>> {
+    let chosen = multi_select_on(input, output, p, items)?;
+    Ok(chosen.into_iter().map(|idx| &items[idx]).collect())
+}
+
+/// Like [`multi_select_items_on`] but specialized to stdin and stdout.
+///
+/// [`multi_select_items_on`]: fn.multi_select_items_on.html
+pub fn multi_select_items<'a, T: Display>(p: &str, items: &'a [T]) -> io::Result<Vec<&'a T>> {
+    multi_select_items_on(&mut io::stdin().lock(), &mut io::stdout(), p, items)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FRUITS: [&str; 5] = ["Apple", "Apricot", "Banana", "Cherry", "Date"];
+
+    #[test]
+    fn selects_by_number_or_prefix() -> io::Result<()> {
+        let mut input = &b"ap\n9\nban\n"[..];
+        let mut output = Vec::new();
+        assert_eq!(select_on(&mut input, &mut output, "Fruit: ", &FRUITS)?, 2);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "1) Apple\n2) Apricot\n3) Banana\n4) Cherry\n5) Date\nFruit: \
+             \"ap\" could be any of: Apple, Apricot\nFruit: \
+             9 is not between 1 and 5\nFruit: ",
+        );
+        assert_eq!(
+            select_on(&mut &b"4\n"[..], &mut io::sink(), "", &FRUITS)?,
+            3
+        );
+        let item = select_item_on(&mut &b"ch\n"[..], &mut io::sink(), "", &FRUITS)?;
+        assert_eq!(*item, "Cherry");
+        Ok(())
+    }
+
+    #[test]
+    fn multi_selects_lists_and_ranges() -> io::Result<()> {
+        let mut input = &b"1,,2\n4-2\n5, 2-3 ,apr\n"[..];
+        let chosen = multi_select_on(&mut input, &mut io::sink(), "Fruits: ", &FRUITS)?;
+        assert_eq!(chosen, vec![1, 2, 4]);
+        let items = multi_select_items_on(&mut &b"5,1\n"[..], &mut io::sink(), "", &FRUITS)?;
+        assert_eq!(items, [&"Apple", &"Date"]);
+        let err = select_on::<&str>(&mut &b"1\n"[..], &mut io::sink(), "", &[])
+            .expect_err("Selected from an empty list");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        Ok(())
+    }
+}
@@ -0,0 +1,493 @@
+//! A streaming reader for delimiter-separated records such as CSV and TSV,
+//! which converts each record into a tuple of `FromStr` types or into any type
+//! that implements [`FromRecord`].
+//!
+//! Fields are separated by a delimiter character and may be enclosed in quotes,
+//! in which case they can contain delimiters, line breaks, and doubled quotes
+//! standing for a single quote. An optional escape character makes the
+//! character after it literal. Records are read one line at a time, so inputs
+//! of any size can be processed.
+//!
+//! [`FromRecord`]: trait.FromRecord.html
+
+use std::{
+    error, fmt,
+    io::{self, BufRead, Error},
+    marker::PhantomData,
+    str::FromStr,
+};
+
+/// The reason a field of a record couldn't be converted, along with its
+/// 1-based column.
+#[derive(Debug)]
+pub struct FieldError {
+    column: usize,
+    source: Box<dyn error::Error + Send + Sync>,
+}
+
+impl FieldError {
+    /// Create an error for the 1-based `column`.
+    pub fn new<E>(column: usize, source: E) -> Self
+    where
+        E: Into<Box<dyn error::Error + Send + Sync>>,
+    {
+        Self {
+            column,
+            source: source.into(),
+        }
+    }
+
+    /// The 1-based column of the field that couldn't be converted.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.source)
+    }
+}
+
+impl error::Error for FieldError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// The reason a record couldn't be read, along with its 1-based row and
+/// column. [`RecordReader`] wraps this in an `io::Error` of kind
+/// `InvalidData`.
+///
+/// [`RecordReader`]: struct.RecordReader.html
+#[derive(Debug)]
+pub struct RecordError {
+    row: usize,
+    column: usize,
+    source: Box<dyn error::Error + Send + Sync>,
+}
+
+impl RecordError {
+    /// The 1-based number of the record, not counting skipped blank lines.
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    /// The 1-based column of the offending field.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "row {}, column {}: {}",
+            self.row, self.column, self.source
+        )
+    }
+}
+
+impl error::Error for RecordError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// The fields of a single record.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Record {
+    fields: Vec<String>,
+}
+
+impl Record {
+    /// The number of fields in the record.
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Whether the record has no fields. Records read by a [`RecordReader`]
+    /// always have at least one.
+    ///
+    /// [`RecordReader`]: struct.RecordReader.html
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Get the field at the 0-based `idx`.
+    pub fn get(&self, idx: usize) -> Option<&str> {
+        self.fields.get(idx).map(String::as_str)
+    }
+
+    /// Iterate over the fields.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.fields.iter().map(String::as_str)
+    }
+
+    /// Convert the field at the 0-based `idx` into a T.
+    pub fn parse<T>(&self, idx: usize) -> Result<T, FieldError>
+    where
+        T: FromStr,
+        <T as FromStr>::Err: Into<Box<dyn error::Error + Send + Sync>>,
+    {
+        let field = self
+            .get(idx)
+            .ok_or_else(|| FieldError::new(idx + 1, "Missing field"))?;
+        field.parse().map_err(|e| FieldError::new(idx + 1, e))
+    }
+
+    /// Check that the record has exactly `len` fields.
+    pub fn expect_len(&self, len: usize) -> Result<(), FieldError> {
+        if self.len() == len {
+            Ok(())
+        } else {
+            let msg = format!("Expected {} fields, found {}", len, self.len());
+            Err(FieldError::new(usize::min(self.len(), len) + 1, msg))
+        }
+    }
+}
+
+/// A type which can be built from the fields of a [`Record`].
+///
+/// [`Record`]: struct.Record.html
+pub trait FromRecord: Sized {
+    /// Convert `record` into a `Self`.
+    fn from_record(record: &Record) -> Result<Self, FieldError>;
+}
+
+macro_rules! from_record_tuple {
+    ($len:expr; $($t:ident $idx:tt),+) => {
+        impl<$($t),+> FromRecord for ($($t,)+)
+        where
+            $(
+                $t: FromStr,
+                <$t as FromStr>::Err: Into<Box<dyn error::Error + Send + Sync>>,
+            )+
+        {
+            fn from_record(record: &Record) -> Result<Self, FieldError> {
+                record.expect_len($len)?;
+                Ok(($(record.parse::<$t>($idx)?,)+))
+            }
+        }
+    };
+}
+
+from_record_tuple!(1; A 0);
+from_record_tuple!(2; A 0, B 1);
+from_record_tuple!(3; A 0, B 1, C 2);
+from_record_tuple!(4; A 0, B 1, C 2, D 3);
+from_record_tuple!(5; A 0, B 1, C 2, D 3, E 4);
+from_record_tuple!(6; A 0, B 1, C 2, D 3, E 4, F 5);
+from_record_tuple!(7; A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+from_record_tuple!(8; A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+impl FromRecord for Record {
+    fn from_record(record: &Record) -> Result<Self, FieldError> {
+        Ok(record.clone())
+    }
+}
+
+impl FromRecord for Vec<String> {
+    fn from_record(record: &Record) -> Result<Self, FieldError> {
+        Ok(record.fields.clone())
+    }
+}
+
+/**
+ * Reads delimiter-separated records from a [`BufRead`]. By default, fields
+ * are separated by commas, may be quoted with `"`, and have no escape
+ * character. Blank lines between records are skipped.
+ *
+ * ```
+ * # use extended_io::records::RecordReader;
+ * let mut reader = RecordReader::new(&b"1,\"one, uno\"\n2,two\n"[..]);
+ * assert_eq!(reader.read::<(u32, String)>()?, Some((1, "one, uno".to_string())));
+ * assert_eq!(reader.read::<(u32, String)>()?, Some((2, "two".to_string())));
+ * assert_eq!(reader.read::<(u32, String)>()?, None);
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ *
+ * [`BufRead`]: /std/io/trait.BufRead.html
+ */
+pub struct RecordReader<R> {
+    src: R,
+    delimiter: char,
+    quote: Option<char>,
+    escape: Option<char>,
+    trim: bool,
+    row: usize,
+    line: String,
+    record: Record,
+}
+
+impl<R: BufRead> RecordReader<R> {
+    /// Create a reader for comma-separated records from the specified bit
+    /// source.
+    pub fn new(src: R) -> Self {
+        Self {
+            src,
+            delimiter: ',',
+            quote: Some('"'),
+            escape: None,
+            trim: false,
+            row: 0,
+            line: String::new(),
+            record: Record::default(),
+        }
+    }
+
+    /// Separate fields with `delimiter` instead of a comma.
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Quote fields with `quote` instead of `"`, or disable quoting.
+    pub fn quote(mut self, quote: Option<char>) -> Self {
+        self.quote = quote;
+        self
+    }
+
+    /// Treat the character after `escape` as literal. An escape character at
+    /// the end of a line continues the field on the next line.
+    pub fn escape(mut self, escape: Option<char>) -> Self {
+        self.escape = escape;
+        self
+    }
+
+    /// Remove whitespace around fields. Whitespace inside quotes is kept.
+    pub fn trim(mut self) -> Self {
+        self.trim = true;
+        self
+    }
+
+    /// The number of records read so far.
+    pub fn row(&self) -> usize {
+        self.row
+    }
+
+    /// Unwrap the reader, returning the underlying bit source.
+    pub fn into_inner(self) -> R {
+        self.src
+    }
+
+    fn error<E>(&self, column: usize, source: E) -> Error
+    where
+        E: Into<Box<dyn error::Error + Send + Sync>>,
+    {
+        let err = RecordError {
+            row: self.row,
+            column,
+            source: source.into(),
+        };
+        Error::new(io::ErrorKind::InvalidData, err)
+    }
+
+    /// Read the next line into `self.line` without its terminator. Returns
+    /// `false` if the source is exhausted.
+    fn next_line(&mut self) -> io::Result<bool> {
+        self.line.clear();
+        if self.src.read_line(&mut self.line)? == 0 {
+            return Ok(false);
+        }
+        if self.line.ends_with('\n') {
+            self.line.pop();
+            if self.line.ends_with('\r') {
+                self.line.pop();
+            }
+        }
+        Ok(true)
+    }
+
+    /// Read the fields of the next record. Returns `None` if there are no more
+    /// records.
+    pub fn read_record(&mut self) -> io::Result<Option<&Record>> {
+        loop {
+            if !self.next_line()? {
+                return Ok(None);
+            }
+            if !self.line.trim().is_empty() {
+                break;
+            }
+        }
+        self.row += 1;
+        self.record.fields.clear();
+        let mut field = String::new();
+        let mut at_start = true;
+        let mut in_quotes = false;
+        let mut after_quote = false;
+        loop {
+            let line = std::mem::take(&mut self.line);
+            let mut chars = line.chars();
+            let mut continued = false;
+            while let Some(c) = chars.next() {
+                if Some(c) == self.escape && (in_quotes || !after_quote) {
+                    match chars.next() {
+                        Some(c) => field.push(c),
+                        None => continued = true,
+                    }
+                    at_start = false;
+                } else if in_quotes {
+                    if Some(c) != self.quote {
+                        field.push(c);
+                    } else if chars.as_str().starts_with(c) {
+                        chars.next();
+                        field.push(c);
+                    } else {
+                        in_quotes = false;
+                        after_quote = true;
+                    }
+                } else if c == self.delimiter {
+                    self.finish_field(&mut field, after_quote);
+                    at_start = true;
+                    after_quote = false;
+                } else if self.trim && (at_start || after_quote) && c.is_whitespace() {
+                } else if after_quote {
+                    let column = self.record.len() + 1;
+                    let msg = format!("Unexpected {:?} after a closing quote", c);
+                    return Err(self.error(column, msg));
+                } else if at_start && Some(c) == self.quote {
+                    in_quotes = true;
+                    at_start = false;
+                } else {
+                    field.push(c);
+                    at_start = false;
+                }
+            }
+            self.line = line;
+            if !(in_quotes || continued) {
+                break;
+            }
+            field.push('\n');
+            if !self.next_line()? {
+                let column = self.record.len() + 1;
+                return Err(self.error(column, "Unterminated field at end of input"));
+            }
+        }
+        self.finish_field(&mut field, after_quote);
+        Ok(Some(&self.record))
+    }
+
+    fn finish_field(&mut self, field: &mut String, quoted: bool) {
+        let mut field = std::mem::take(field);
+        if self.trim && !quoted {
+            field.truncate(field.trim_end().len());
+        }
+        self.record.fields.push(field);
+    }
+
+    /// Read the next record and convert it into a T. Returns `None` if there
+    /// are no more records.
+    pub fn read<T: FromRecord>(&mut self) -> io::Result<Option<T>> {
+        let row = self.row + 1;
+        match self.read_record()? {
+            Some(record) => match T::from_record(record) {
+                Ok(x) => Ok(Some(x)),
+                Err(e) => {
+                    let err = RecordError {
+                        row,
+                        column: e.column,
+                        source: e.source,
+                    };
+                    Err(Error::new(io::ErrorKind::InvalidData, err))
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Iterate over the remaining records, converting each into a T. The
+    /// iterator moves on to the next record after a record that can't be
+    /// converted, but ends after any other error.
+    pub fn records<T: FromRecord>(&mut self) -> Records<'_, R, T> {
+        Records {
+            reader: self,
+            done: false,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// An iterator over the records of a [`RecordReader`].
+///
+/// [`RecordReader`]: struct.RecordReader.html
+pub struct Records<'a, R, T> {
+    reader: &'a mut RecordReader<R>,
+    done: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, R: BufRead, T: FromRecord> Iterator for Records<'a, R, T> {
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let ret = self.reader.read().transpose();
+        if let Some(Err(e)) = &ret {
+            let is_record_error = e
+                .get_ref()
+                .is_some_and(|e| e.downcast_ref::<RecordError>().is_some());
+            self.done = !is_record_error;
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::BufReader;
+
+    #[test]
+    fn reads_quoted_and_escaped_fields() -> io::Result<()> {
+        let src = "a,\"b \"\"c\"\", d\",e\n\n\"multi\r\nline\",,\\,\n";
+        let mut reader =
+            RecordReader::new(BufReader::with_capacity(3, src.as_bytes())).escape(Some('\\'));
+        let record = reader.read_record()?.unwrap();
+        assert_eq!(record.iter().collect::<Vec<_>>(), ["a", "b \"c\", d", "e"]);
+        let record = reader.read_record()?.unwrap();
+        assert_eq!(record.iter().collect::<Vec<_>>(), ["multi\nline", "", ","]);
+        assert!(reader.read_record()?.is_none());
+        assert_eq!(reader.row(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn reads_tuples_from_tsv() -> io::Result<()> {
+        let src = &b" 1\t one \t1.5\n2\ttwo\tx\n3\tthree\n4\t\"four\"\t4\n"[..];
+        let mut reader = RecordReader::new(src).delimiter('\t').trim();
+        let records = reader
+            .records::<(u8, String, f32)>()
+            .map(|r| r.map_err(|e| e.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            records,
+            vec![
+                Ok((1, "one".to_string(), 1.5)),
+                Err("row 2, column 3: invalid float literal".to_string()),
+                Err("row 3, column 3: Expected 3 fields, found 2".to_string()),
+                Ok((4, "four".to_string(), 4.0)),
+            ],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reports_malformed_quotes() {
+        let mut reader = RecordReader::new(&b"ok\n1,\"2\"3\n"[..]);
+        reader.read_record().unwrap();
+        let err = reader
+            .read_record()
+            .expect_err("Accepted text after a quote");
+        let err = err.into_inner().unwrap().downcast::<RecordError>().unwrap();
+        assert_eq!((err.row(), err.column()), (2, 2));
+
+        let mut reader = RecordReader::new(&b"\"open\n"[..]);
+        let err = reader.read_record().expect_err("Accepted an open quote");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
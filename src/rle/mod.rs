@@ -0,0 +1,538 @@
+//! Run-length encoding adapters for two byte-oriented schemes: [`PackBits`],
+//! as used by TIFF, PSD, and MacPaint, and the simpler [`ByteRle`].
+//!
+//! An [`RleReader`] decodes what it reads and an [`RleWriter`] encodes what's
+//! written to it. Since a short encoded stream can describe a huge amount of
+//! output, the reader stops with an error once the decoded output would pass
+//! a limit.
+//!
+//! [`PackBits`]: struct.PackBits.html
+//! [`ByteRle`]: struct.ByteRle.html
+//! [`RleReader`]: struct.RleReader.html
+//! [`RleWriter`]: struct.RleWriter.html
+
+use std::{
+    io::{self, BufRead, Error, Read, Write},
+    marker::PhantomData,
+};
+
+/// The most decoded bytes an [`RleReader`] produces unless a different limit
+/// is set: 256 MiB.
+///
+/// [`RleReader`]: struct.RleReader.html
+pub const DEFAULT_LIMIT: u64 = 256 * 1024 * 1024;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// What the header byte of an encoded chunk calls for.
+#[doc(hidden)]
+#[derive(Clone, Copy, Debug)]
+pub enum Op {
+    /// Copy this many bytes through.
+    Literal(u64),
+    /// Repeat the next byte this many times.
+    Run(u64),
+    /// Nothing.
+    Nop,
+}
+
+/**
+ * A run-length encoding scheme.
+ *
+ * This trait is sealed and implemented for [`PackBits`] and [`ByteRle`].
+ *
+ * [`PackBits`]: struct.PackBits.html
+ * [`ByteRle`]: struct.ByteRle.html
+ */
+pub trait RunLength: private::Sealed {
+    #[doc(hidden)]
+    const NAME: &'static str;
+
+    /// The shortest run worth encoding as a run rather than as literal bytes.
+    #[doc(hidden)]
+    const MIN_RUN: usize;
+
+    #[doc(hidden)]
+    const MAX_RUN: usize;
+
+    #[doc(hidden)]
+    fn decode_header(header: u8) -> Option<Op>;
+
+    #[doc(hidden)]
+    fn encode_literal(bytes: &[u8], out: &mut Vec<u8>);
+
+    #[doc(hidden)]
+    fn encode_run(len: usize, byte: u8, out: &mut Vec<u8>);
+}
+
+/**
+ * The PackBits scheme. Each chunk starts with a header byte `n`, read as an
+ * `i8`: 0 to 127 means that the next `n + 1` bytes are copied through, -1 to
+ * -127 means that the next byte is repeated `1 - n` times, and -128 is
+ * ignored.
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct PackBits;
+
+impl private::Sealed for PackBits {}
+
+impl RunLength for PackBits {
+    const NAME: &'static str = "PackBits";
+    const MIN_RUN: usize = 3;
+    const MAX_RUN: usize = 128;
+
+    fn decode_header(header: u8) -> Option<Op> {
+        Some(match header as i8 {
+            -128 => Op::Nop,
+            n @ 0..=127 => Op::Literal(n as u64 + 1),
+            n => Op::Run((1 - i64::from(n)) as u64),
+        })
+    }
+
+    fn encode_literal(bytes: &[u8], out: &mut Vec<u8>) {
+        for chunk in bytes.chunks(128) {
+            out.push(chunk.len() as u8 - 1);
+            out.extend_from_slice(chunk);
+        }
+    }
+
+    fn encode_run(len: usize, byte: u8, out: &mut Vec<u8>) {
+        out.push((257 - len) as u8);
+        out.push(byte);
+    }
+}
+
+/// A plain byte-oriented scheme, in which every chunk is a count from 1 to
+/// 255 followed by the byte to repeat that many times.
+#[derive(Clone, Copy, Debug)]
+pub struct ByteRle;
+
+impl private::Sealed for ByteRle {}
+
+impl RunLength for ByteRle {
+    const NAME: &'static str = "RLE";
+    const MIN_RUN: usize = 1;
+    const MAX_RUN: usize = 255;
+
+    fn decode_header(header: u8) -> Option<Op> {
+        match header {
+            0 => None,
+            n => Some(Op::Run(n.into())),
+        }
+    }
+
+    fn encode_literal(bytes: &[u8], out: &mut Vec<u8>) {
+        for &byte in bytes {
+            Self::encode_run(1, byte, out);
+        }
+    }
+
+    fn encode_run(len: usize, byte: u8, out: &mut Vec<u8>) {
+        out.push(len as u8);
+        out.push(byte);
+    }
+}
+
+/// A decoder of PackBits data.
+pub type PackBitsReader<R> = RleReader<R, PackBits>;
+
+/// An encoder of PackBits data.
+pub type PackBitsWriter<W> = RleWriter<W, PackBits>;
+
+/// A decoder of [`ByteRle`] data.
+///
+/// [`ByteRle`]: struct.ByteRle.html
+pub type ByteRleReader<R> = RleReader<R, ByteRle>;
+
+/// An encoder of [`ByteRle`] data.
+///
+/// [`ByteRle`]: struct.ByteRle.html
+pub type ByteRleWriter<W> = RleWriter<W, ByteRle>;
+
+#[derive(Clone, Copy, Debug)]
+enum State {
+    /// Expecting a header byte.
+    Header,
+    /// In the middle of a literal with this many bytes left.
+    Literal(u64),
+    /// Expecting the byte of a run of this length.
+    RunByte(u64),
+    /// In the middle of a run of this byte with this many bytes left.
+    Run(u8, u64),
+}
+
+/**
+ * A bit source that decodes run-length encoded data from the underlying bit
+ * source. The end of the underlying bit source between chunks is the end of
+ * the data.
+ *
+ * Returns an error of kind `InvalidData` if a header is invalid or the
+ * decoded output would pass the limit, which is checked before the chunk
+ * that would pass it is decoded, and of kind `UnexpectedEof` if the
+ * underlying bit source is exhausted partway through a chunk.
+ *
+ * ```
+ * # use extended_io::rle::PackBitsReader;
+ * # use std::io::Read;
+ * let mut src = PackBitsReader::new(&b"\xfe\xaa\x02\x80\x00\x2a"[..]);
+ * let mut out = Vec::new();
+ * src.read_to_end(&mut out)?;
+ * assert_eq!(out, b"\xaa\xaa\xaa\x80\x00\x2a");
+ *
+ * let mut src = PackBitsReader::new(&b"\x81\x00\x81\x00"[..]).limit(200);
+ * let err = src.read_to_end(&mut Vec::new()).expect_err("Passed the limit");
+ * assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ */
+#[derive(Debug)]
+pub struct RleReader<R, S> {
+    inner: R,
+    state: State,
+    limit: u64,
+    decoded: u64,
+    scheme: PhantomData<S>,
+}
+
+impl<R: BufRead, S: RunLength> RleReader<R, S> {
+    /// Decode the data read from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            state: State::Header,
+            limit: DEFAULT_LIMIT,
+            decoded: 0,
+            scheme: PhantomData,
+        }
+    }
+
+    /// Fail once the decoded output would pass `limit` bytes, instead of the
+    /// default of [`DEFAULT_LIMIT`].
+    ///
+    /// [`DEFAULT_LIMIT`]: constant.DEFAULT_LIMIT.html
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Get a reference to the underlying bit source.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying bit source.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwrap the reader, returning the underlying bit source.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: BufRead, S: RunLength> Read for RleReader<R, S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            match self.state {
+                State::Header => {
+                    let header = loop {
+                        match self.inner.fill_buf() {
+                            Ok([]) => return Ok(0),
+                            Ok([header, ..]) => break *header,
+                            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                            Err(e) => return Err(e),
+                        }
+                    };
+                    self.inner.consume(1);
+                    let (len, state): (u64, fn(u64) -> State) = match S::decode_header(header) {
+                        Some(Op::Literal(len)) => (len, State::Literal),
+                        Some(Op::Run(len)) => (len, State::RunByte),
+                        Some(Op::Nop) => continue,
+                        None => {
+                            let msg = format!("Invalid {} header 0x{:02x}", S::NAME, header);
+                            return Err(Error::new(io::ErrorKind::InvalidData, msg));
+                        }
+                    };
+                    if self.decoded + len > self.limit {
+                        let msg = format!(
+                            "Decoded {} output exceeds the limit of {} bytes",
+                            S::NAME,
+                            self.limit,
+                        );
+                        return Err(Error::new(io::ErrorKind::InvalidData, msg));
+                    }
+                    self.decoded += len;
+                    self.state = state(len);
+                }
+                State::Literal(left) => {
+                    let max = buf.len().min(left as usize);
+                    let n = self.inner.read(&mut buf[..max])?;
+                    if n == 0 {
+                        let msg = format!("{} literal cut short", S::NAME);
+                        return Err(Error::new(io::ErrorKind::UnexpectedEof, msg));
+                    }
+                    self.state = match left - n as u64 {
+                        0 => State::Header,
+                        left => State::Literal(left),
+                    };
+                    return Ok(n);
+                }
+                State::RunByte(len) => {
+                    self.state = State::Run(crate::read_u8(&mut self.inner)?, len);
+                }
+                State::Run(byte, left) => {
+                    let n = buf.len().min(left as usize);
+                    buf[..n].fill(byte);
+                    self.state = match left - n as u64 {
+                        0 => State::Header,
+                        left => State::Run(byte, left),
+                    };
+                    return Ok(n);
+                }
+            }
+        }
+    }
+}
+
+/**
+ * A bit sink that run-length encodes what's written to it. The end of a run
+ * can't be known until the next different byte arrives, so a few bytes are
+ * held back between writes. The encoding must be completed with [`finish`].
+ * Flushing writes out everything held back, at the cost of possibly
+ * splitting a run in two.
+ *
+ * ```
+ * # use extended_io::rle::ByteRleWriter;
+ * # use std::io::Write;
+ * let mut out = ByteRleWriter::new(Vec::new());
+ * out.write_all(b"aaa")?;
+ * out.write_all(b"ab")?;
+ * assert_eq!(out.finish()?, b"\x04a\x01b");
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ *
+ * [`finish`]: #method.finish
+ */
+#[derive(Debug)]
+pub struct RleWriter<W, S> {
+    inner: W,
+    pending: Vec<u8>,
+    /// Encoded bytes that haven't been written to the bit sink yet.
+    out: Vec<u8>,
+    scheme: PhantomData<S>,
+}
+
+impl<W: Write, S: RunLength> RleWriter<W, S> {
+    /// Encode the data written to `inner`.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            pending: Vec::new(),
+            out: Vec::new(),
+            scheme: PhantomData,
+        }
+    }
+
+    /// Get a reference to the underlying bit sink.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying bit sink. Bytes held back
+    /// by the encoder haven't been written to it yet.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Write out everything held back, then flush and return the underlying
+    /// bit sink.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.encode(true);
+        self.write_out()?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Write out the encoded bytes, keeping whatever the bit sink didn't
+    /// accept if it fails.
+    fn write_out(&mut self) -> io::Result<()> {
+        while !self.out.is_empty() {
+            match self.inner.write(&self.out) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.out.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the pending bytes into the encoded output, except for a run or
+    /// literal at the end that might continue unless `all` is set.
+    fn encode(&mut self, all: bool) {
+        let data = &self.pending;
+        let mut idx = 0;
+        let mut literal = 0;
+        while idx < data.len() {
+            let max = S::MAX_RUN.min(data.len() - idx);
+            let run = data[idx..idx + max]
+                .iter()
+                .take_while(|&&byte| byte == data[idx])
+                .count();
+            if !all && idx + run == data.len() && run < S::MAX_RUN {
+                break;
+            }
+            if run >= S::MIN_RUN {
+                S::encode_literal(&data[literal..idx], &mut self.out);
+                S::encode_run(run, data[idx], &mut self.out);
+                idx += run;
+                literal = idx;
+            } else {
+                idx += run;
+                while idx - literal >= 128 {
+                    S::encode_literal(&data[literal..literal + 128], &mut self.out);
+                    literal += 128;
+                }
+            }
+        }
+        if all {
+            S::encode_literal(&data[literal..idx], &mut self.out);
+            literal = idx;
+        }
+        self.pending.drain(..literal);
+    }
+}
+
+impl<W: Write, S: RunLength> Write for RleWriter<W, S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Nothing of `buf` is taken if earlier output still can't be written.
+        self.write_out()?;
+        self.pending.extend_from_slice(buf);
+        self.encode(false);
+        // `buf` is now held by the writer, and any error comes up again on
+        // the next write or flush.
+        let _ = self.write_out();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.encode(true);
+        self.write_out()?;
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::BufReader;
+
+    fn sample() -> Vec<u8> {
+        let mut data = b"abc".to_vec();
+        data.extend_from_slice(&[0; 300]);
+        data.extend((0..400).map(|x| (x % 7) as u8));
+        data.extend_from_slice(b"xxyyyz");
+        data
+    }
+
+    fn round_trip<S: RunLength>() -> io::Result<()> {
+        let data = sample();
+        let mut out = RleWriter::<_, S>::new(Vec::new());
+        out.write_all(&data)?;
+        let encoded = out.finish()?;
+        // Writing a byte at a time mustn't change the encoding.
+        let mut out = RleWriter::<_, S>::new(Vec::new());
+        for &byte in &data {
+            out.write_all(&[byte])?;
+        }
+        assert_eq!(out.finish()?, encoded, "{}", S::NAME);
+
+        let mut src = RleReader::<_, S>::new(BufReader::with_capacity(3, &encoded[..]));
+        let mut decoded = Vec::new();
+        src.read_to_end(&mut decoded)?;
+        assert_eq!(decoded, data, "{}", S::NAME);
+        Ok(())
+    }
+
+    #[test]
+    fn round_trips_data() -> io::Result<()> {
+        round_trip::<PackBits>()?;
+        round_trip::<ByteRle>()?;
+
+        // The example from Apple's Technical Note TN1023.
+        let unpacked = b"\xaa\xaa\xaa\x80\x00\x2a\xaa\xaa\xaa\xaa\x80\x00\x2a\x22\
+                         \xaa\xaa\xaa\xaa\xaa\xaa\xaa\xaa\xaa\xaa";
+        let packed = b"\xfe\xaa\x02\x80\x00\x2a\xfd\xaa\x03\x80\x00\x2a\x22\xf7\xaa";
+        let mut out = PackBitsWriter::new(Vec::new());
+        out.write_all(unpacked)?;
+        assert_eq!(out.finish()?, packed);
+        let mut decoded = Vec::new();
+        PackBitsReader::new(&packed[..]).read_to_end(&mut decoded)?;
+        assert_eq!(decoded, unpacked);
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_bad_data() -> io::Result<()> {
+        let mut src = ByteRleReader::new(&b"\x02a\x00a"[..]);
+        let mut buf = [0; 8];
+        assert_eq!(src.read(&mut buf)?, 2);
+        let err = src.read(&mut buf).expect_err("Accepted a zero count");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut src = ByteRleReader::new(&b"\xffa\xffa"[..]).limit(300);
+        let err = src
+            .read_to_end(&mut Vec::new())
+            .expect_err("Passed the limit");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        for truncated in [&b"\x05abc"[..], b"\xfe"] {
+            let err = PackBitsReader::new(truncated)
+                .read_to_end(&mut Vec::new())
+                .expect_err("Accepted truncated data");
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn survives_sink_errors() -> io::Result<()> {
+        /// A sink that fails every other write.
+        struct Flaky(Vec<u8>, bool);
+
+        impl Write for Flaky {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.1 = !self.1;
+                if self.1 {
+                    return Err(io::Error::other("Not now"));
+                }
+                self.0.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let data = sample();
+        let mut out = ByteRleWriter::new(Flaky(Vec::new(), false));
+        for piece in data.chunks(50) {
+            while out.write(piece).is_err() {}
+        }
+        while out.flush().is_err() {}
+        let encoded = out.finish()?.0;
+        let mut decoded = Vec::new();
+        ByteRleReader::new(&encoded[..]).read_to_end(&mut decoded)?;
+        assert_eq!(decoded, data);
+        Ok(())
+    }
+}
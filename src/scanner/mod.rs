@@ -0,0 +1,244 @@
+//! A [`Scanner`] which splits the contents of a [`BufRead`] into
+//! whitespace-separated tokens and parses them, regardless of how the tokens
+//! are distributed across lines.
+//!
+//! [`BufRead`]: /std/io/trait.BufRead.html
+//! [`Scanner`]: struct.Scanner.html
+
+use std::{
+    error,
+    io::{self, BufRead, Error},
+    str::{self, FromStr},
+};
+
+/**
+ * Reads whitespace-separated tokens from a [`BufRead`]. Tokens are separated by
+ * any amount of ASCII whitespace, including line breaks, and are collected
+ * into a buffer that is reused from one token to the next.
+ *
+ * ```
+ * # use extended_io::scanner::Scanner;
+ * let mut scanner = Scanner::new(&b"3 4\n5 x 2.5\n"[..]);
+ * let (a, b, c): (i32, i32, i32) = scanner.next()?;
+ * assert_eq!((a, b, c), (3, 4, 5));
+ * let (s, f) = scanner.next::<(String, f64)>()?;
+ * assert_eq!((s.as_str(), f), ("x", 2.5));
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ *
+ * [`BufRead`]: /std/io/trait.BufRead.html
+ */
+pub struct Scanner<R> {
+    src: R,
+    token: Vec<u8>,
+}
+
+impl<R: BufRead> Scanner<R> {
+    /// Create a scanner that reads from the specified bit source.
+    pub fn new(src: R) -> Self {
+        Self {
+            src,
+            token: Vec::new(),
+        }
+    }
+
+    /// Get a reference to the underlying bit source.
+    pub fn get_ref(&self) -> &R {
+        &self.src
+    }
+
+    /// Get a mutable reference to the underlying bit source. Reading from it
+    /// directly affects which tokens the scanner sees next.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.src
+    }
+
+    /// Unwrap the scanner, returning the underlying bit source.
+    pub fn into_inner(self) -> R {
+        self.src
+    }
+
+    /**
+     * Read the next token. The returned slice borrows the scanner's internal
+     * buffer, so it's only valid until the next read. Returns an error of kind
+     * `UnexpectedEof` if there are no more tokens or `InvalidData` if the
+     * token isn't valid UTF-8.
+     */
+    pub fn next_token(&mut self) -> io::Result<&str> {
+        self.token.clear();
+        loop {
+            let buf = self.src.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+            let start = if self.token.is_empty() {
+                match buf.iter().position(|b| !b.is_ascii_whitespace()) {
+                    Some(idx) => idx,
+                    None => {
+                        let len = buf.len();
+                        self.src.consume(len);
+                        continue;
+                    }
+                }
+            } else {
+                0
+            };
+            match buf[start..].iter().position(u8::is_ascii_whitespace) {
+                Some(len) => {
+                    self.token.extend_from_slice(&buf[start..start + len]);
+                    self.src.consume(start + len);
+                    break;
+                }
+                None => {
+                    self.token.extend_from_slice(&buf[start..]);
+                    let len = buf.len();
+                    self.src.consume(len);
+                }
+            }
+        }
+        if self.token.is_empty() {
+            return Err(Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Scanner: no more tokens",
+            ));
+        }
+        str::from_utf8(&self.token).map_err(|e| Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Read the next token and convert it into a T. Unlike [`next`], this
+    /// accepts any `FromStr` type but only reads a single token.
+    ///
+    /// [`next`]: #method.next
+    pub fn next_parsed<T>(&mut self) -> io::Result<T>
+    where
+        T: FromStr,
+        <T as FromStr>::Err: Into<Box<dyn error::Error + Send + Sync>>,
+    {
+        match self.next_token()?.parse() {
+            Ok(x) => Ok(x),
+            Err(e) => Err(Error::new(io::ErrorKind::InvalidData, e)),
+        }
+    }
+
+    /// Read as many tokens as a T needs and convert them into a T.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next<T: Scan>(&mut self) -> io::Result<T> {
+        T::scan(self)
+    }
+
+    /// Read `n` consecutive Ts.
+    pub fn next_n<T: Scan>(&mut self, n: usize) -> io::Result<Vec<T>> {
+        (0..n).map(|_| self.next()).collect()
+    }
+
+    /**
+     * Read the rest of the current line, without the line terminator. Since
+     * reading a token stops just before the whitespace that follows it, the
+     * first call after reading the last token on a line returns an empty
+     * string. Returns an error of kind `UnexpectedEof` if the source is
+     * already exhausted.
+     */
+    pub fn next_line(&mut self) -> io::Result<String> {
+        let mut line = String::new();
+        if self.src.read_line(&mut line)? == 0 {
+            return Err(Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Scanner: no more lines",
+            ));
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(line)
+    }
+}
+
+/// A type which can be read from a [`Scanner`] as one or more tokens.
+///
+/// [`Scanner`]: struct.Scanner.html
+pub trait Scan: Sized {
+    /// Read a `Self` from the specified scanner.
+    fn scan<R: BufRead>(scanner: &mut Scanner<R>) -> io::Result<Self>;
+}
+
+macro_rules! scan_from_str {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl Scan for $t {
+                fn scan<R: BufRead>(scanner: &mut Scanner<R>) -> io::Result<Self> {
+                    scanner.next_parsed()
+                }
+            }
+        )*
+    };
+}
+
+scan_from_str!(
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64, bool, char, String,
+);
+
+macro_rules! scan_tuple {
+    ($($t:ident),+) => {
+        impl<$($t: Scan),+> Scan for ($($t,)+) {
+            fn scan<R: BufRead>(scanner: &mut Scanner<R>) -> io::Result<Self> {
+                Ok(($($t::scan(scanner)?,)+))
+            }
+        }
+    };
+}
+
+scan_tuple!(A);
+scan_tuple!(A, B);
+scan_tuple!(A, B, C);
+scan_tuple!(A, B, C, D);
+scan_tuple!(A, B, C, D, E);
+scan_tuple!(A, B, C, D, E, F);
+scan_tuple!(A, B, C, D, E, F, G);
+scan_tuple!(A, B, C, D, E, F, G, H);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::BufReader;
+
+    #[test]
+    fn reads_tokens_across_lines() -> io::Result<()> {
+        // A tiny buffer forces tokens to straddle `fill_buf` boundaries.
+        let src = BufReader::with_capacity(2, &b"  12 345\n\n-6\t78 "[..]);
+        let mut scanner = Scanner::new(src);
+        assert_eq!(scanner.next_n::<i32>(4)?, vec![12, 345, -6, 78]);
+        assert_eq!(
+            scanner
+                .next::<u8>()
+                .expect_err("Read past the last token")
+                .kind(),
+            io::ErrorKind::UnexpectedEof,
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reads_tuples_and_lines() -> io::Result<()> {
+        let mut scanner = Scanner::new(&b"7 seven 7.5\r\nthe rest\nnope"[..]);
+        let (n, s, f) = scanner.next::<(u32, String, f64)>()?;
+        assert_eq!((n, s.as_str(), f), (7, "seven", 7.5));
+        assert_eq!(scanner.next_line()?, "");
+        assert_eq!(scanner.next_line()?, "the rest");
+        assert_eq!(
+            scanner
+                .next::<i64>()
+                .expect_err("Parsed a word as a number")
+                .kind(),
+            io::ErrorKind::InvalidData,
+        );
+        assert_eq!(
+            scanner.next_line().expect_err("Read past the end").kind(),
+            io::ErrorKind::UnexpectedEof,
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,887 @@
+//! A decoder for binary layouts that are described at runtime rather than in
+//! Rust code, which is handy when exploring an unfamiliar format.
+//!
+//! A [`Schema`] is written as a list of fields, each of which is a type
+//! optionally followed by a name. Fields are separated by commas, semicolons,
+//! or line breaks, and `#` starts a comment that runs to the end of the line.
+//! The types are:
+//!
+//! * `u8` through `u128`, `i8` through `i128`, `f32`, and `f64`, optionally
+//!   followed by `be`, `le`, or `ne` for the byte order. As with the crate's
+//!   primitive readers, the default is big-endian.
+//! * `bytes[n]`, which is `n` raw bytes.
+//! * `bytes<t>`, which is raw bytes preceded by their length as the integer
+//!   type `t`.
+//! * `n * t` (or `n × t`), which is `n` consecutive values of type `t`.
+//! * `{ ... }`, which is a nested list of fields.
+//!
+//! Each `n` is either a number or the name of an integer field that comes
+//! earlier in the same list or in an enclosing one. Decoding produces a tree
+//! of [`Value`]s, which can be printed with `{}` or, indented, with `{:#}`.
+//! Since lengths come from the data, decoding stops with an error once it has
+//! produced [`DEFAULT_MAX_VALUES`] values, unless a different limit is set with
+//! [`Schema::max_values`].
+//!
+//! ```
+//! # use extended_io::schema::Schema;
+//! let schema: Schema = "u32le magic, u16be count, count * {u8 tag, bytes<u8> data} entries"
+//!     .parse()
+//!     .unwrap();
+//! let data = b"\x7fELF\x00\x02\x01\x01A\x02\x00";
+//! let value = schema.decode(&mut &data[..])?;
+//! assert_eq!(
+//!     value.to_string(),
+//!     "{magic: 1179403647, count: 2, entries: [{tag: 1, data: <41>}, {tag: 2, data: <>}]}",
+//! );
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! [`Schema`]: struct.Schema.html
+//! [`Value`]: enum.Value.html
+//! [`DEFAULT_MAX_VALUES`]: constant.DEFAULT_MAX_VALUES.html
+//! [`Schema::max_values`]: struct.Schema.html#method.max_values
+
+use std::{
+    convert::TryFrom,
+    error, fmt,
+    io::{self, Error, Read},
+    str::FromStr,
+};
+
+use crate::{
+    read_f32, read_f32_le, read_f32_ne, read_f64, read_f64_le, read_f64_ne, read_u128,
+    read_u128_le, read_u128_ne, read_uint, ByteOrder,
+};
+
+/// The most values decoded from one input unless a different limit is set.
+pub const DEFAULT_MAX_VALUES: u64 = 1024 * 1024;
+
+/// A fixed-size number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Primitive {
+    /// An integer `bytes` bytes wide.
+    Int {
+        /// Whether the integer is two's complement signed.
+        signed: bool,
+        /// The width of the integer: 1, 2, 4, 8, or 16.
+        bytes: usize,
+        /// The byte order of the integer.
+        order: ByteOrder,
+    },
+    /// An IEEE 754 floating-point number `bytes` bytes wide.
+    Float {
+        /// The width of the number: 4 or 8.
+        bytes: usize,
+        /// The byte order of the number.
+        order: ByteOrder,
+    },
+}
+
+/// The number of bytes or repetitions in a field.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Length {
+    /// A number given in the schema.
+    Fixed(u64),
+    /// The value of an earlier integer field.
+    Field(String),
+}
+
+/// The type of a field.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Type {
+    /// A single number.
+    Primitive(Primitive),
+    /// Raw bytes of the specified length.
+    Bytes(Length),
+    /// Raw bytes preceded by their length as the specified integer type.
+    PrefixedBytes(Primitive),
+    /// Consecutive values of the same type.
+    Repeat(Length, Box<Type>),
+    /// A nested list of fields.
+    Struct(Vec<Field>),
+}
+
+/// A field of a [`Schema`]. Unnamed fields are given the name `_n`, where `n`
+/// is their position in the enclosing list.
+///
+/// [`Schema`]: struct.Schema.html
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Field {
+    /// The name of the field, if it has one.
+    pub name: Option<String>,
+    /// The type of the field.
+    pub ty: Type,
+}
+
+/// A decoded value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// An unsigned integer.
+    Unsigned(u128),
+    /// A signed integer.
+    Signed(i128),
+    /// A floating-point number.
+    Float(f64),
+    /// Raw bytes.
+    Bytes(Vec<u8>),
+    /// The values of a repeated field.
+    Array(Vec<Value>),
+    /// The names and values of a list of fields.
+    Struct(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Get the value of the named field of a struct.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        match self {
+            Self::Struct(fields) => fields.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Get the value of an integer as a `u64`, if it's a non-negative integer
+    /// that fits.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Self::Unsigned(x) => u64::try_from(x).ok(),
+            Self::Signed(x) => u64::try_from(x).ok(),
+            _ => None,
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        let pretty = f.alternate();
+        let (open, sep, close) = if pretty {
+            (
+                "\n".to_string() + &"  ".repeat(indent + 1),
+                ",\n".to_string() + &"  ".repeat(indent + 1),
+                ",\n".to_string() + &"  ".repeat(indent),
+            )
+        } else {
+            (String::new(), ", ".to_string(), String::new())
+        };
+        match self {
+            Self::Unsigned(x) => write!(f, "{}", x),
+            Self::Signed(x) => write!(f, "{}", x),
+            Self::Float(x) => write!(f, "{:?}", x),
+            Self::Bytes(bytes) => {
+                f.write_str("<")?;
+                for (idx, byte) in bytes.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "{:02x}", byte)?;
+                }
+                f.write_str(">")
+            }
+            Self::Array(values) if values.is_empty() => f.write_str("[]"),
+            Self::Array(values) => {
+                write!(f, "[{}", open)?;
+                for (idx, value) in values.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(&sep)?;
+                    }
+                    value.fmt_indented(f, indent + 1)?;
+                }
+                write!(f, "{}]", close)
+            }
+            Self::Struct(fields) if fields.is_empty() => f.write_str("{}"),
+            Self::Struct(fields) => {
+                write!(f, "{{{}", open)?;
+                for (idx, (name, value)) in fields.iter().enumerate() {
+                    if idx > 0 {
+                        f.write_str(&sep)?;
+                    }
+                    write!(f, "{}: ", name)?;
+                    value.fmt_indented(f, indent + 1)?;
+                }
+                write!(f, "{}}}", close)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// The reason a schema couldn't be parsed, along with the 1-based line and
+/// column where the problem was found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaError {
+    line: usize,
+    column: usize,
+    msg: String,
+}
+
+impl SchemaError {
+    /// The 1-based line of the problem.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The 1-based column of the problem.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.msg)
+    }
+}
+
+impl error::Error for SchemaError {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Ident(String),
+    Number(u64),
+    Symbol(char),
+    Separator,
+    End,
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+    token: Token,
+    token_pos: (usize, usize),
+    /// The names of the fields that are in scope, innermost list last, along
+    /// with whether each is an integer.
+    scopes: Vec<Vec<(String, bool)>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Result<Self, SchemaError> {
+        let mut parser = Self {
+            chars: text.chars().peekable(),
+            line: 1,
+            column: 1,
+            token: Token::End,
+            token_pos: (1, 1),
+            scopes: Vec::new(),
+        };
+        parser.advance()?;
+        Ok(parser)
+    }
+
+    fn error(&self, msg: impl Into<String>) -> SchemaError {
+        SchemaError {
+            line: self.token_pos.0,
+            column: self.token_pos.1,
+            msg: msg.into(),
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn advance(&mut self) -> Result<(), SchemaError> {
+        loop {
+            match self.chars.peek() {
+                Some('#') => {
+                    while self.chars.peek().is_some_and(|&c| c != '\n') {
+                        self.bump();
+                    }
+                }
+                Some(&c) if c.is_whitespace() && c != '\n' => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+        self.token_pos = (self.line, self.column);
+        let c = match self.bump() {
+            Some(c) => c,
+            None => {
+                self.token = Token::End;
+                return Ok(());
+            }
+        };
+        self.token = match c {
+            '\n' | ',' | ';' => Token::Separator,
+            '{' | '}' | '[' | ']' | '<' | '>' | '*' => Token::Symbol(c),
+            '×' => Token::Symbol('*'),
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut ident = c.to_string();
+                while let Some(&c) = self.chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    ident.push(c);
+                    self.bump();
+                }
+                Token::Ident(ident)
+            }
+            c if c.is_ascii_digit() => {
+                let mut digits = c.to_string();
+                while let Some(&c) = self.chars.peek() {
+                    if !c.is_ascii_alphanumeric() {
+                        break;
+                    }
+                    digits.push(c);
+                    self.bump();
+                }
+                let parsed = match digits.strip_prefix("0x") {
+                    Some(hex) => u64::from_str_radix(hex, 16),
+                    None => digits.parse(),
+                };
+                Token::Number(
+                    parsed.map_err(|_| self.error(format!("Invalid number {:?}", digits)))?,
+                )
+            }
+            c => return Err(self.error(format!("Unexpected {:?}", c))),
+        };
+        Ok(())
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), SchemaError> {
+        if self.token != Token::Symbol(symbol) {
+            return Err(self.error(format!("Expected {:?}", symbol)));
+        }
+        self.advance()
+    }
+
+    fn skip_separators(&mut self) -> Result<(), SchemaError> {
+        while self.token == Token::Separator {
+            self.advance()?;
+        }
+        Ok(())
+    }
+
+    /// Parse a list of fields, which ends at `}` or the end of the text.
+    fn fields(&mut self) -> Result<Vec<Field>, SchemaError> {
+        self.scopes.push(Vec::new());
+        let mut fields = Vec::new();
+        self.skip_separators()?;
+        while !matches!(self.token, Token::End | Token::Symbol('}')) {
+            let ty = self.ty()?;
+            let name = match &self.token {
+                Token::Ident(name) => {
+                    let name = name.clone();
+                    self.advance()?;
+                    Some(name)
+                }
+                _ => None,
+            };
+            if !matches!(
+                self.token,
+                Token::Separator | Token::End | Token::Symbol('}')
+            ) {
+                return Err(self.error("Expected a separator after the field"));
+            }
+            self.skip_separators()?;
+            let is_int = matches!(ty, Type::Primitive(Primitive::Int { .. }));
+            let scope_name = name.clone().unwrap_or_else(|| format!("_{}", fields.len()));
+            self.scopes.last_mut().unwrap().push((scope_name, is_int));
+            fields.push(Field { name, ty });
+        }
+        self.scopes.pop();
+        Ok(fields)
+    }
+
+    fn length(&mut self) -> Result<Length, SchemaError> {
+        let len = match &self.token {
+            Token::Number(n) => Length::Fixed(*n),
+            Token::Ident(name) => {
+                let found = self
+                    .scopes
+                    .iter()
+                    .rev()
+                    .find_map(|scope| scope.iter().rev().find(|(n, _)| n == name));
+                match found {
+                    Some((_, true)) => Length::Field(name.clone()),
+                    Some((_, false)) => {
+                        return Err(self.error(format!("{:?} is not an integer", name)))
+                    }
+                    None => return Err(self.error(format!("No earlier field named {:?}", name))),
+                }
+            }
+            _ => return Err(self.error("Expected a length")),
+        };
+        self.advance()?;
+        Ok(len)
+    }
+
+    fn ty(&mut self) -> Result<Type, SchemaError> {
+        let ident = match &self.token {
+            Token::Symbol('{') => {
+                self.advance()?;
+                let fields = self.fields()?;
+                self.expect('}')?;
+                return Ok(Type::Struct(fields));
+            }
+            Token::Number(_) => {
+                let len = self.length()?;
+                self.expect('*')?;
+                return Ok(Type::Repeat(len, Box::new(self.ty()?)));
+            }
+            Token::Ident(ident) => ident.clone(),
+            _ => return Err(self.error("Expected a type")),
+        };
+        if ident == "bytes" {
+            self.advance()?;
+            return match self.token {
+                Token::Symbol('[') => {
+                    self.advance()?;
+                    let len = self.length()?;
+                    self.expect(']')?;
+                    Ok(Type::Bytes(len))
+                }
+                Token::Symbol('<') => {
+                    self.advance()?;
+                    let prefix = match self.primitive()? {
+                        prefix @ Primitive::Int { .. } => prefix,
+                        Primitive::Float { .. } => {
+                            return Err(self.error("Length prefixes must be integers"))
+                        }
+                    };
+                    self.expect('>')?;
+                    Ok(Type::PrefixedBytes(prefix))
+                }
+                _ => Err(self.error("Expected '[' or '<' after \"bytes\"")),
+            };
+        }
+        if let Some(primitive) = parse_primitive(&ident) {
+            self.advance()?;
+            return Ok(Type::Primitive(primitive));
+        }
+        let len = self.length()?;
+        self.expect('*')?;
+        Ok(Type::Repeat(len, Box::new(self.ty()?)))
+    }
+
+    fn primitive(&mut self) -> Result<Primitive, SchemaError> {
+        let primitive = match &self.token {
+            Token::Ident(ident) => parse_primitive(ident),
+            _ => None,
+        };
+        let primitive = primitive.ok_or_else(|| self.error("Expected a number type"))?;
+        self.advance()?;
+        Ok(primitive)
+    }
+}
+
+fn parse_primitive(ident: &str) -> Option<Primitive> {
+    let (base, order) = if let Some(base) = ident.strip_suffix("le") {
+        (base, ByteOrder::Little)
+    } else if let Some(base) = ident.strip_suffix("be") {
+        (base, ByteOrder::Big)
+    } else if let Some(base) = ident.strip_suffix("ne") {
+        (base, ByteOrder::Native)
+    } else {
+        (ident, ByteOrder::Big)
+    };
+    let (kind, bits) = base.split_at(base.find(|c: char| c.is_ascii_digit())?);
+    let bytes = match bits {
+        "8" => 1,
+        "16" => 2,
+        "32" => 4,
+        "64" => 8,
+        "128" => 16,
+        _ => return None,
+    };
+    match kind {
+        "u" => Some(Primitive::Int {
+            signed: false,
+            bytes,
+            order,
+        }),
+        "i" => Some(Primitive::Int {
+            signed: true,
+            bytes,
+            order,
+        }),
+        "f" if bytes == 4 || bytes == 8 => Some(Primitive::Float { bytes, order }),
+        _ => None,
+    }
+}
+
+impl fmt::Display for Primitive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (kind, bytes, order) = match *self {
+            Self::Int {
+                signed: false,
+                bytes,
+                order,
+            } => ('u', bytes, order),
+            Self::Int {
+                signed: true,
+                bytes,
+                order,
+            } => ('i', bytes, order),
+            Self::Float { bytes, order } => ('f', bytes, order),
+        };
+        let order = match order {
+            ByteOrder::Big => "be",
+            ByteOrder::Little => "le",
+            ByteOrder::Native => "ne",
+        };
+        write!(f, "{}{}{}", kind, bytes * 8, order)
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed(n) => write!(f, "{}", n),
+            Self::Field(name) => f.write_str(name),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Primitive(primitive) => write!(f, "{}", primitive),
+            Self::Bytes(len) => write!(f, "bytes[{}]", len),
+            Self::PrefixedBytes(prefix) => write!(f, "bytes<{}>", prefix),
+            Self::Repeat(len, ty) => write!(f, "{} * {}", len, ty),
+            Self::Struct(fields) => {
+                f.write_str("{")?;
+                write_fields(f, fields)?;
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+fn write_fields(f: &mut fmt::Formatter<'_>, fields: &[Field]) -> fmt::Result {
+    for (idx, field) in fields.iter().enumerate() {
+        if idx > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{}", field.ty)?;
+        if let Some(name) = &field.name {
+            write!(f, " {}", name)?;
+        }
+    }
+    Ok(())
+}
+
+/// A binary layout, described in the language documented at the
+/// [module level].
+///
+/// [module level]: index.html
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Schema {
+    fields: Vec<Field>,
+    max_values: u64,
+}
+
+impl FromStr for Schema {
+    type Err = SchemaError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser::new(text)?;
+        let fields = parser.fields()?;
+        if parser.token != Token::End {
+            return Err(parser.error("Unmatched '}'"));
+        }
+        Ok(Self {
+            fields,
+            max_values: DEFAULT_MAX_VALUES,
+        })
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_fields(f, &self.fields)
+    }
+}
+
+/// Prefix the message of `e` with the path of the field being decoded.
+fn at(path: &str, e: Error) -> Error {
+    Error::new(e.kind(), format!("{}: {}", path, e))
+}
+
+fn read_primitive(src: &mut dyn Read, primitive: Primitive) -> io::Result<Value> {
+    use ByteOrder::*;
+    Ok(match primitive {
+        Primitive::Int {
+            signed,
+            bytes: 16,
+            order,
+        } => {
+            let raw = match order {
+                Big => read_u128(src)?,
+                Little => read_u128_le(src)?,
+                Native => read_u128_ne(src)?,
+            };
+            if signed {
+                Value::Signed(raw as i128)
+            } else {
+                Value::Unsigned(raw)
+            }
+        }
+        Primitive::Int {
+            signed,
+            bytes,
+            order,
+        } => {
+            let raw = read_uint(src, bytes, order)?;
+            if signed {
+                let shift = 64 - bytes * 8;
+                Value::Signed((((raw << shift) as i64) >> shift).into())
+            } else {
+                Value::Unsigned(raw.into())
+            }
+        }
+        Primitive::Float { bytes: 4, order } => Value::Float(
+            match order {
+                Big => read_f32(src)?,
+                Little => read_f32_le(src)?,
+                Native => read_f32_ne(src)?,
+            }
+            .into(),
+        ),
+        Primitive::Float { order, .. } => Value::Float(match order {
+            Big => read_f64(src)?,
+            Little => read_f64_le(src)?,
+            Native => read_f64_ne(src)?,
+        }),
+    })
+}
+
+fn too_many_values() -> Error {
+    Error::new(io::ErrorKind::InvalidData, "Too many values to decode")
+}
+
+/// Read exactly `len` bytes without trusting `len` enough to allocate it all
+/// up front.
+fn read_exact_bytes(src: &mut dyn Read, len: u64) -> io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    src.take(len).read_to_end(&mut buf)?;
+    if (buf.len() as u64) < len {
+        let msg = format!("Expected {} bytes, found {}", len, buf.len());
+        return Err(Error::new(io::ErrorKind::UnexpectedEof, msg));
+    }
+    Ok(buf)
+}
+
+struct Decoder<'a> {
+    src: &'a mut dyn Read,
+    /// The fields decoded so far in each list being decoded, innermost last.
+    scopes: Vec<Vec<(String, Value)>>,
+    /// How many more values may be decoded.
+    budget: u64,
+}
+
+impl Decoder<'_> {
+    fn length(&self, len: &Length) -> io::Result<u64> {
+        match len {
+            Length::Fixed(n) => Ok(*n),
+            Length::Field(name) => {
+                let value = self
+                    .scopes
+                    .iter()
+                    .rev()
+                    .find_map(|scope| scope.iter().rev().find(|(n, _)| n == name))
+                    .map(|(_, value)| value)
+                    .ok_or_else(|| {
+                        let msg = format!("No earlier field named {}", name);
+                        Error::new(io::ErrorKind::InvalidInput, msg)
+                    })?;
+                value.as_u64().ok_or_else(|| {
+                    let msg = format!("{} = {} isn't a valid length", name, value);
+                    Error::new(io::ErrorKind::InvalidData, msg)
+                })
+            }
+        }
+    }
+
+    fn fields(&mut self, fields: &[Field], path: &str) -> io::Result<Value> {
+        self.scopes.push(Vec::new());
+        for (idx, field) in fields.iter().enumerate() {
+            let name = match &field.name {
+                Some(name) => name.clone(),
+                None => format!("_{}", idx),
+            };
+            let path = if path.is_empty() {
+                name.clone()
+            } else {
+                format!("{}.{}", path, name)
+            };
+            let value = self.ty(&field.ty, &path)?;
+            self.scopes.last_mut().unwrap().push((name, value));
+        }
+        Ok(Value::Struct(self.scopes.pop().unwrap()))
+    }
+
+    fn ty(&mut self, ty: &Type, path: &str) -> io::Result<Value> {
+        if self.budget == 0 {
+            return Err(at(path, too_many_values()));
+        }
+        self.budget -= 1;
+        match ty {
+            Type::Primitive(primitive) => {
+                read_primitive(self.src, *primitive).map_err(|e| at(path, e))
+            }
+            Type::Bytes(len) => {
+                let len = self.length(len).map_err(|e| at(path, e))?;
+                Ok(Value::Bytes(
+                    read_exact_bytes(self.src, len).map_err(|e| at(path, e))?,
+                ))
+            }
+            Type::PrefixedBytes(prefix) => {
+                let len = read_primitive(self.src, *prefix).map_err(|e| at(path, e))?;
+                let len = len.as_u64().ok_or_else(|| {
+                    let msg = format!("{}: {} isn't a valid length", path, len);
+                    Error::new(io::ErrorKind::InvalidData, msg)
+                })?;
+                Ok(Value::Bytes(
+                    read_exact_bytes(self.src, len).map_err(|e| at(path, e))?,
+                ))
+            }
+            Type::Repeat(len, ty) => {
+                let len = self.length(len).map_err(|e| at(path, e))?;
+                // Checked up front so that a huge count of empty structs
+                // fails without looping over them.
+                if len > self.budget {
+                    return Err(at(path, too_many_values()));
+                }
+                let mut values = Vec::with_capacity(usize::min(len as usize, 1024));
+                for idx in 0..len {
+                    values.push(self.ty(ty, &format!("{}[{}]", path, idx))?);
+                }
+                Ok(Value::Array(values))
+            }
+            Type::Struct(fields) => self.fields(fields, path),
+        }
+    }
+}
+
+impl Schema {
+    /// The top-level fields of the schema.
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Refuse to decode more than `max` values in total, counting each
+    /// field, array element, and nested struct, instead of the default of
+    /// [`DEFAULT_MAX_VALUES`]. Exceeding it is an error of kind
+    /// `InvalidData`.
+    ///
+    /// [`DEFAULT_MAX_VALUES`]: constant.DEFAULT_MAX_VALUES.html
+    pub fn max_values(mut self, max: u64) -> Self {
+        self.max_values = max;
+        self
+    }
+
+    /// Decode a value of this layout from the specified bit source, returning
+    /// a [`Value::Struct`] of the top-level fields. Errors are prefixed with
+    /// the path of the field that was being decoded, such as
+    /// `entries[3].data`.
+    ///
+    /// [`Value::Struct`]: enum.Value.html#variant.Struct
+    pub fn decode(&self, src: &mut dyn Read) -> io::Result<Value> {
+        let mut decoder = Decoder {
+            src,
+            scopes: Vec::new(),
+            budget: self.max_values,
+        };
+        decoder.fields(&self.fields, "")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SCHEMA: &str = "
+        # A made-up container format.
+        u32le magic
+        u16 count
+        count × {
+            u8 tag; i16le; f32
+            bytes<u8> data
+        } entries
+        2 * bytes[2]
+    ";
+
+    #[test]
+    fn parses_and_prints_schemas() {
+        let schema: Schema = SCHEMA.parse().unwrap();
+        let text = schema.to_string();
+        assert_eq!(
+            text,
+            "u32le magic, u16be count, \
+             count * {u8be tag, i16le, f32be, bytes<u8be> data} entries, 2 * bytes[2]",
+        );
+        assert_eq!(text.parse::<Schema>().unwrap(), schema);
+
+        let err = "u8 a\n  bytes[b]".parse::<Schema>().unwrap_err();
+        assert_eq!((err.line(), err.column()), (2, 9));
+        assert!("f32 x, x * u8".parse::<Schema>().is_err());
+        assert!("u8 a }".parse::<Schema>().is_err());
+        assert!("u7".parse::<Schema>().is_err());
+    }
+
+    #[test]
+    fn decodes_values() -> io::Result<()> {
+        let schema: Schema = SCHEMA.parse().unwrap();
+        let mut data = vec![1, 0, 0, 0, 0, 2];
+        data.extend_from_slice(&[7, 0xFE, 0xFF, 0x3F, 0xC0, 0, 0, 2, 0xAB, 0xCD]);
+        data.extend_from_slice(&[8, 1, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(b"abcd");
+        let value = schema.decode(&mut &data[..])?;
+        assert_eq!(value.get("count"), Some(&Value::Unsigned(2)));
+        assert_eq!(
+            format!("{:#}", value),
+            "{
+  magic: 1,
+  count: 2,
+  entries: [
+    {
+      tag: 7,
+      _1: -2,
+      _2: 1.5,
+      data: <ab cd>,
+    },
+    {
+      tag: 8,
+      _1: 1,
+      _2: 0.0,
+      data: <>,
+    },
+  ],
+  _3: [
+    <61 62>,
+    <63 64>,
+  ],
+}",
+        );
+
+        let err = schema
+            .decode(&mut &data[..15])
+            .expect_err("Decoded truncated data");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(err.to_string().starts_with("entries[0].data: "));
+
+        let err = schema
+            .max_values(8)
+            .decode(&mut &data[..])
+            .expect_err("Decoded past the limit");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let schema: Schema = "u64 n, n * {}".parse().unwrap();
+        let err = schema
+            .decode(&mut &[0xFF; 8][..])
+            .expect_err("Decoded a huge repeat");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        Ok(())
+    }
+}
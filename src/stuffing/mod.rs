@@ -0,0 +1,564 @@
+//! Framing by byte stuffing, as used on serial lines: [COBS] and [SLIP].
+//!
+//! Both schemes end each frame with a delimiter byte that never appears
+//! inside an encoded frame, so a reader that loses its place, or meets a
+//! corrupted frame, can pick up again at the next delimiter.
+//!
+//! A [`StuffedReader`] delivers one frame at a time, either whole through
+//! [`read_frame`] or through its `Read` implementation, which ends at the end
+//! of the current frame so that the crate's `read_*` functions can decode its
+//! contents. A [`StuffedWriter`] does the reverse.
+//!
+//! [COBS]: https://en.wikipedia.org/wiki/Consistent_Overhead_Byte_Stuffing
+//! [SLIP]: https://www.rfc-editor.org/rfc/rfc1055
+//! [`StuffedReader`]: struct.StuffedReader.html
+//! [`StuffedWriter`]: struct.StuffedWriter.html
+//! [`read_frame`]: struct.StuffedReader.html#method.read_frame
+
+use std::{
+    io::{self, BufRead, Error, Read, Write},
+    marker::PhantomData,
+};
+
+use crate::frame::DEFAULT_MAX_FRAME_SIZE;
+
+mod private {
+    pub trait Sealed {}
+}
+
+/**
+ * A byte-stuffing scheme, along with the state needed to decode a frame a
+ * byte at a time.
+ *
+ * This trait is sealed and implemented for [`Cobs`] and [`Slip`].
+ *
+ * [`Cobs`]: struct.Cobs.html
+ * [`Slip`]: struct.Slip.html
+ */
+pub trait Stuffing: Default + private::Sealed {
+    /// The byte that ends each frame.
+    const DELIMITER: u8;
+
+    #[doc(hidden)]
+    const NAME: &'static str;
+
+    #[doc(hidden)]
+    fn push(&mut self, byte: u8, out: &mut Vec<u8>) -> Result<(), &'static str>;
+
+    #[doc(hidden)]
+    fn finish(&mut self) -> Result<(), &'static str>;
+
+    #[doc(hidden)]
+    fn encode(payload: &[u8], out: &mut Vec<u8>);
+}
+
+/// Consistent Overhead Byte Stuffing, with frames ending in `0x00`.
+#[derive(Clone, Debug, Default)]
+pub struct Cobs {
+    /// The number of data bytes left in the current block.
+    remaining: u8,
+    /// Whether a zero goes between the current block and the next one.
+    zero_pending: bool,
+}
+
+impl private::Sealed for Cobs {}
+
+impl Stuffing for Cobs {
+    const DELIMITER: u8 = 0x00;
+    const NAME: &'static str = "COBS";
+
+    fn push(&mut self, byte: u8, out: &mut Vec<u8>) -> Result<(), &'static str> {
+        if self.remaining > 0 {
+            out.push(byte);
+            self.remaining -= 1;
+        } else {
+            if self.zero_pending {
+                out.push(0);
+            }
+            self.remaining = byte - 1;
+            self.zero_pending = byte != 0xFF;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), &'static str> {
+        let remaining = self.remaining;
+        *self = Self::default();
+        if remaining == 0 {
+            Ok(())
+        } else {
+            Err("block cut short")
+        }
+    }
+
+    fn encode(payload: &[u8], out: &mut Vec<u8>) {
+        let mut code_idx = out.len();
+        out.push(0);
+        let mut code = 1;
+        for &byte in payload {
+            if byte != 0 {
+                out.push(byte);
+                code += 1;
+            }
+            if byte == 0 || code == 0xFF {
+                out[code_idx] = code;
+                code_idx = out.len();
+                out.push(0);
+                code = 1;
+            }
+        }
+        out[code_idx] = code;
+        out.push(Self::DELIMITER);
+    }
+}
+
+/// The Serial Line Internet Protocol of RFC 1055, with frames ending in
+/// `0xC0`. SLIP can't represent an empty frame, so empty frames are skipped
+/// when reading.
+#[derive(Clone, Debug, Default)]
+pub struct Slip {
+    escaped: bool,
+}
+
+impl Slip {
+    const ESC: u8 = 0xDB;
+    const ESC_END: u8 = 0xDC;
+    const ESC_ESC: u8 = 0xDD;
+}
+
+impl private::Sealed for Slip {}
+
+impl Stuffing for Slip {
+    const DELIMITER: u8 = 0xC0;
+    const NAME: &'static str = "SLIP";
+
+    fn push(&mut self, byte: u8, out: &mut Vec<u8>) -> Result<(), &'static str> {
+        if self.escaped {
+            self.escaped = false;
+            match byte {
+                Self::ESC_END => out.push(Self::DELIMITER),
+                Self::ESC_ESC => out.push(Self::ESC),
+                _ => return Err("invalid escape sequence"),
+            }
+        } else if byte == Self::ESC {
+            self.escaped = true;
+        } else {
+            out.push(byte);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), &'static str> {
+        let escaped = self.escaped;
+        *self = Self::default();
+        if escaped {
+            Err("frame ends in an escape")
+        } else {
+            Ok(())
+        }
+    }
+
+    fn encode(payload: &[u8], out: &mut Vec<u8>) {
+        for &byte in payload {
+            match byte {
+                Self::DELIMITER => out.extend_from_slice(&[Self::ESC, Self::ESC_END]),
+                Self::ESC => out.extend_from_slice(&[Self::ESC, Self::ESC_ESC]),
+                _ => out.push(byte),
+            }
+        }
+        out.push(Self::DELIMITER);
+    }
+}
+
+/// A reader of COBS frames.
+pub type CobsReader<R> = StuffedReader<R, Cobs>;
+
+/// A reader of SLIP frames.
+pub type SlipReader<R> = StuffedReader<R, Slip>;
+
+/// A writer of COBS frames.
+pub type CobsWriter<W> = StuffedWriter<W, Cobs>;
+
+/// A writer of SLIP frames.
+pub type SlipWriter<W> = StuffedWriter<W, Slip>;
+
+/**
+ * A bit source of byte-stuffed frames.
+ *
+ * [`next_frame`] moves on to the next frame, after which reads return its
+ * contents and then report the end of the source once it's used up. A frame
+ * that's corrupted or larger than the maximum size produces an error of kind
+ * `InvalidData`, and the reader then skips to the next delimiter, so the
+ * following call picks up with the next frame. Empty frames, such as the
+ * delimiters some senders use to flush line noise, are skipped.
+ *
+ * If the underlying bit source returns an error partway through a frame, the
+ * bytes decoded so far are kept and the next call picks up where the last
+ * one left off.
+ *
+ * ```
+ * # use extended_io::{self as eio, stuffing::{CobsReader, CobsWriter}};
+ * let mut out = CobsWriter::new(Vec::new());
+ * eio::write_u32(&mut out, 0x0100_0000)?;
+ * out.end_frame()?;
+ * out.write_frame(b"\x00\x00")?;
+ * let encoded = out.into_inner();
+ * assert_eq!(encoded, b"\x02\x01\x01\x01\x01\x00\x01\x01\x01\x00");
+ *
+ * let mut src = CobsReader::new(&encoded[..]);
+ * assert!(src.next_frame()?);
+ * assert_eq!(eio::read_u32(&mut src)?, 0x0100_0000);
+ * assert_eq!(src.read_frame()?, Some(&b"\x00\x00"[..]));
+ * assert_eq!(src.read_frame()?, None);
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ *
+ * [`next_frame`]: #method.next_frame
+ */
+#[derive(Debug)]
+pub struct StuffedReader<R, S> {
+    inner: R,
+    state: S,
+    max_frame_size: usize,
+    /// The frame decoded so far.
+    frame: Vec<u8>,
+    /// How much of a complete frame has been read.
+    pos: usize,
+    /// Whether `frame` holds a complete frame.
+    complete: bool,
+    /// Whether any bytes of the current frame have been received.
+    started: bool,
+    /// Whether the rest of a corrupted frame is being skipped.
+    skipping: bool,
+}
+
+impl<R: BufRead, S: Stuffing> StuffedReader<R, S> {
+    /// Read frames from `inner`.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            state: S::default(),
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            frame: Vec::new(),
+            pos: 0,
+            complete: false,
+            started: false,
+            skipping: false,
+        }
+    }
+
+    /// Reject frames that decode to more than `max` bytes, instead of the
+    /// default of [`DEFAULT_MAX_FRAME_SIZE`].
+    ///
+    /// [`DEFAULT_MAX_FRAME_SIZE`]: ../frame/constant.DEFAULT_MAX_FRAME_SIZE.html
+    pub fn max_frame_size(mut self, max: usize) -> Self {
+        self.max_frame_size = max;
+        self
+    }
+
+    /// Get a reference to the underlying bit source.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying bit source.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwrap the reader, returning the underlying bit source. Any partly
+    /// received frame is lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /**
+     * Discard the rest of the current frame and move on to the next one.
+     * Returns `false` if the bit source is exhausted between frames.
+     *
+     * Returns an error of kind `InvalidData` if the next frame is corrupted
+     * or too large, and of kind `UnexpectedEof` if the bit source is
+     * exhausted partway through a frame.
+     */
+    pub fn next_frame(&mut self) -> io::Result<bool> {
+        if self.complete {
+            self.frame.clear();
+            self.pos = 0;
+            self.complete = false;
+        }
+        loop {
+            let buf = match self.inner.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if buf.is_empty() {
+                self.skipping = false;
+                if !self.started {
+                    return Ok(false);
+                }
+                let msg = format!("{} frame cut short", S::NAME);
+                return Err(self.discard(Error::new(io::ErrorKind::UnexpectedEof, msg)));
+            }
+            let end = buf.iter().position(|&byte| byte == S::DELIMITER);
+            let used = end.map_or(buf.len(), |end| end + 1);
+            let mut result = Ok(());
+            if !self.skipping {
+                let raw = &buf[..end.unwrap_or(used)];
+                self.started |= !raw.is_empty();
+                for &byte in raw {
+                    result = self.state.push(byte, &mut self.frame);
+                    if result.is_ok() && self.frame.len() > self.max_frame_size {
+                        result = Err("frame exceeds the maximum size");
+                    }
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                if result.is_ok() && end.is_some() && self.started {
+                    result = self.state.finish();
+                }
+            }
+            self.inner.consume(used);
+            if let Err(msg) = result {
+                self.skipping = end.is_none();
+                let msg = format!("Invalid {} frame: {}", S::NAME, msg);
+                return Err(self.discard(Error::new(io::ErrorKind::InvalidData, msg)));
+            }
+            if end.is_some() {
+                if self.skipping {
+                    self.skipping = false;
+                } else if self.started {
+                    self.started = false;
+                    self.complete = true;
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    /// Read the whole of the next frame, or `None` if the bit source is
+    /// exhausted between frames. Errors are as for [`next_frame`].
+    ///
+    /// [`next_frame`]: #method.next_frame
+    pub fn read_frame(&mut self) -> io::Result<Option<&[u8]>> {
+        if !self.next_frame()? {
+            return Ok(None);
+        }
+        self.pos = self.frame.len();
+        Ok(Some(&self.frame))
+    }
+
+    /// Forget the current frame, returning `e`.
+    fn discard(&mut self, e: Error) -> Error {
+        self.state = S::default();
+        self.frame.clear();
+        self.started = false;
+        e
+    }
+}
+
+impl<R: BufRead, S: Stuffing> Read for StuffedReader<R, S> {
+    /// Read from the current frame. Reports the end of the source at the end
+    /// of the frame, or if there's no current frame.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.complete {
+            return Ok(0);
+        }
+        let n = (&self.frame[self.pos..]).read(buf)?;
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/**
+ * A bit sink of byte-stuffed frames.
+ *
+ * Bytes written through the `Write` implementation are collected into a
+ * frame, which is encoded and written to the underlying bit sink by
+ * [`end_frame`]. Flushing doesn't end the frame. If the bit sink fails
+ * partway through a frame, the rest of it is kept and written by the next
+ * call to any method that writes.
+ *
+ * [`end_frame`]: #method.end_frame
+ */
+#[derive(Debug)]
+pub struct StuffedWriter<W, S> {
+    inner: W,
+    frame: Vec<u8>,
+    /// The part of the encoded frame that hasn't been written yet.
+    out: Vec<u8>,
+    stuffing: PhantomData<S>,
+}
+
+impl<W: Write, S: Stuffing> StuffedWriter<W, S> {
+    /// Write frames to `inner`.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            frame: Vec::new(),
+            out: Vec::new(),
+            stuffing: PhantomData,
+        }
+    }
+
+    /// Get a reference to the underlying bit sink.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying bit sink.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwrap the writer, returning the underlying bit sink. Bytes written
+    /// since the last frame ended, and the unwritten part of a frame the bit
+    /// sink failed to accept, are lost.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Write out the rest of a frame that the bit sink failed to accept,
+    /// and forget the frame once all of it has been written.
+    fn write_out(&mut self) -> io::Result<()> {
+        if self.out.is_empty() {
+            return Ok(());
+        }
+        while !self.out.is_empty() {
+            match self.inner.write(&self.out) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.out.drain(..n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.frame.clear();
+        Ok(())
+    }
+
+    /// Encode the bytes written since the last frame ended as a frame, and
+    /// write it to the underlying bit sink. If this fails, calling it again
+    /// carries on writing the same frame.
+    pub fn end_frame(&mut self) -> io::Result<()> {
+        if self.out.is_empty() {
+            S::encode(&self.frame, &mut self.out);
+        }
+        self.write_out()
+    }
+
+    /// Write `payload` and end the frame. Any bytes written since the last
+    /// frame ended form the start of the same frame.
+    ///
+    /// Returns an error without taking `payload` if the rest of an earlier
+    /// frame can't be written. Once `payload` is taken, an error writing it
+    /// comes up on the next call that writes.
+    pub fn write_frame(&mut self, payload: &[u8]) -> io::Result<()> {
+        self.write_out()?;
+        self.frame.extend_from_slice(payload);
+        let _ = self.end_frame();
+        Ok(())
+    }
+}
+
+impl<W: Write, S: Stuffing> Write for StuffedWriter<W, S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The bytes belong to the next frame, so the current one has to be
+        // written out first.
+        self.write_out()?;
+        self.frame.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_out()?;
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::Flaky;
+    use std::{cell::Cell, io::BufReader};
+
+    fn payloads() -> Vec<Vec<u8>> {
+        let mut payloads = vec![
+            b"\x00".to_vec(),
+            b"a\x00\xc0\xdb\xdc\xdd\x00".to_vec(),
+            (1..=254).collect(),
+            (1..=255).collect(),
+        ];
+        payloads.push((0..600).map(|x| (x % 251) as u8).collect());
+        payloads
+    }
+
+    fn round_trip<S: Stuffing>() -> io::Result<()> {
+        let mut out = StuffedWriter::<_, S>::new(Vec::new());
+        for payload in payloads() {
+            out.write_frame(&payload)?;
+        }
+        let encoded = out.into_inner();
+        // Frames arrive a few bytes at a time.
+        let mut src = StuffedReader::<_, S>::new(BufReader::with_capacity(3, &encoded[..]));
+        for payload in payloads() {
+            assert_eq!(src.read_frame()?, Some(&payload[..]), "{}", S::NAME);
+        }
+        assert_eq!(src.read_frame()?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn round_trips_frames() -> io::Result<()> {
+        round_trip::<Cobs>()?;
+        round_trip::<Slip>()?;
+        let mut encoded = Vec::new();
+        Cobs::encode(&[0x11, 0x22, 0x00, 0x33], &mut encoded);
+        assert_eq!(encoded, b"\x03\x11\x22\x02\x33\x00");
+        Ok(())
+    }
+
+    #[test]
+    fn resyncs_after_corruption() -> io::Result<()> {
+        let data = b"\x05ab\x00\x00\x02x\x00\x03\x01\x01\x01\x01\x00\x02y\x00\x02z";
+        let mut src = CobsReader::new(&data[..]).max_frame_size(2);
+        let err = src.next_frame().expect_err("Accepted a short block");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(src.read_frame()?, Some(&b"x"[..]));
+        let err = src.next_frame().expect_err("Accepted an oversized frame");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(src.read_frame()?, Some(&b"y"[..]));
+        let err = src.next_frame().expect_err("Accepted a truncated frame");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let data = b"\xc0ab\xdbx\xc0\xc0\x00\x2a\xdb\xdc\xc0";
+        let mut src = SlipReader::new(BufReader::with_capacity(2, &data[..]));
+        let err = src.next_frame().expect_err("Accepted a bad escape");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(src.next_frame()?);
+        assert_eq!(crate::read_u16(&mut src)?, 42);
+        assert_eq!(crate::read_u8(&mut src)?, 0xc0);
+        assert_eq!(src.read(&mut [0; 4])?, 0);
+        assert!(!src.next_frame()?);
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_frames_whole_despite_sink_errors() -> io::Result<()> {
+        let failing = Cell::new(true);
+        let mut out = CobsWriter::new(Flaky::new(3, &failing));
+        let mut expected = Vec::new();
+        for payload in payloads() {
+            Cobs::encode(&payload, &mut expected);
+            while out.write_frame(&payload).is_err() {}
+        }
+        while out.write(b"ab").is_err() {}
+        Cobs::encode(b"ab", &mut expected);
+        while out.end_frame().is_err() {}
+        while out.flush().is_err() {}
+        assert_eq!(out.into_inner().data, expected);
+        Ok(())
+    }
+}
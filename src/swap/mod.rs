@@ -0,0 +1,241 @@
+//! In-place byte-order conversion of whole slices of numbers, for decoding
+//! large arrays much faster than one `read_*` call per element.
+//!
+//! On x86_64, the conversion uses AVX2 or SSSE3 when the processor supports
+//! them, which is detected at runtime. Everywhere else, and for the elements
+//! left over after the vectorized part, it falls back to swapping one element
+//! at a time.
+
+use std::{
+    io::{self, Read},
+    mem, slice,
+};
+
+use crate::ByteOrder;
+
+/**
+ * A number whose bytes can be reordered in bulk. Every bit pattern of a
+ * `SwapBytes` type is a valid value, so a slice of them can be filled directly
+ * from a bit source.
+ *
+ * This trait is sealed and implemented for the integer types from 16 to 128
+ * bits wide and for `f32` and `f64`.
+ */
+pub trait SwapBytes: Copy + private::Sealed {
+    #[doc(hidden)]
+    fn swap(self) -> Self;
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+macro_rules! swap_bytes {
+    ($($t:ty),*) => {
+        $(
+            impl private::Sealed for $t {}
+
+            impl SwapBytes for $t {
+                fn swap(self) -> Self {
+                    self.swap_bytes()
+                }
+            }
+        )*
+    };
+}
+
+swap_bytes!(u16, u32, u64, u128, i16, i32, i64, i128);
+
+impl private::Sealed for f32 {}
+
+impl SwapBytes for f32 {
+    fn swap(self) -> Self {
+        f32::from_bits(self.to_bits().swap_bytes())
+    }
+}
+
+impl private::Sealed for f64 {}
+
+impl SwapBytes for f64 {
+    fn swap(self) -> Self {
+        f64::from_bits(self.to_bits().swap_bytes())
+    }
+}
+
+fn as_bytes_mut<T: SwapBytes>(vals: &mut [T]) -> &mut [u8] {
+    // SAFETY: every `SwapBytes` type is a plain number with no padding and no
+    // invalid bit patterns, so its bytes may be freely read and written.
+    unsafe { slice::from_raw_parts_mut(vals.as_mut_ptr() as *mut u8, mem::size_of_val(vals)) }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    /// The `pshufb` control mask that reverses each `width`-byte element of a
+    /// 16-byte lane.
+    fn mask(width: usize) -> [u8; 16] {
+        let mut mask = [0; 16];
+        for (idx, byte) in mask.iter_mut().enumerate() {
+            *byte = ((idx / width) * width + (width - 1 - idx % width)) as u8;
+        }
+        mask
+    }
+
+    #[target_feature(enable = "avx2")]
+    unsafe fn swap_avx2(bytes: &mut [u8], width: usize) -> usize {
+        let mask = mask(width);
+        let mask = _mm256_loadu2_m128i(mask.as_ptr() as _, mask.as_ptr() as _);
+        let done = bytes.len() / 32 * 32;
+        for chunk in bytes[..done].chunks_exact_mut(32) {
+            let ptr = chunk.as_mut_ptr() as *mut __m256i;
+            _mm256_storeu_si256(ptr, _mm256_shuffle_epi8(_mm256_loadu_si256(ptr), mask));
+        }
+        done + swap_ssse3(&mut bytes[done..], width)
+    }
+
+    #[target_feature(enable = "ssse3")]
+    unsafe fn swap_ssse3(bytes: &mut [u8], width: usize) -> usize {
+        let mask = _mm_loadu_si128(mask(width).as_ptr() as _);
+        let done = bytes.len() / 16 * 16;
+        for chunk in bytes[..done].chunks_exact_mut(16) {
+            let ptr = chunk.as_mut_ptr() as *mut __m128i;
+            _mm_storeu_si128(ptr, _mm_shuffle_epi8(_mm_loadu_si128(ptr), mask));
+        }
+        done
+    }
+
+    /// Reverse each `width`-byte element of as much of `bytes` as the
+    /// processor's vector extensions allow, returning the number of bytes
+    /// handled. The count is always a multiple of 16.
+    pub(super) fn swap(bytes: &mut [u8], width: usize) -> usize {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: the processor supports AVX2, which implies SSSE3.
+            unsafe { swap_avx2(bytes, width) }
+        } else if is_x86_feature_detected!("ssse3") {
+            // SAFETY: the processor supports SSSE3.
+            unsafe { swap_ssse3(bytes, width) }
+        } else {
+            0
+        }
+    }
+}
+
+/// Reverse the byte order of every element of `vals`.
+pub fn swap_in_place<T: SwapBytes>(vals: &mut [T]) {
+    #[cfg(target_arch = "x86_64")]
+    let vals = {
+        let width = mem::size_of::<T>();
+        let done = x86::swap(as_bytes_mut(vals), width) / width;
+        &mut vals[done..]
+    };
+    for val in vals {
+        *val = val.swap();
+    }
+}
+
+/// Convert every element of `vals` between the specified byte order and the
+/// native one. The conversion is its own inverse, so this works in either
+/// direction.
+pub fn convert_in_place<T: SwapBytes>(vals: &mut [T], order: ByteOrder) {
+    let native = if cfg!(target_endian = "big") {
+        ByteOrder::Big
+    } else {
+        ByteOrder::Little
+    };
+    if order != ByteOrder::Native && order != native {
+        swap_in_place(vals);
+    }
+}
+
+/// Convert every element of `vals` from big-endian to native byte order.
+pub fn from_be_in_place<T: SwapBytes>(vals: &mut [T]) {
+    convert_in_place(vals, ByteOrder::Big);
+}
+
+/// Convert every element of `vals` from little-endian to native byte order.
+pub fn from_le_in_place<T: SwapBytes>(vals: &mut [T]) {
+    convert_in_place(vals, ByteOrder::Little);
+}
+
+/// Convert every element of `vals` from native to big-endian byte order.
+pub fn to_be_in_place<T: SwapBytes>(vals: &mut [T]) {
+    convert_in_place(vals, ByteOrder::Big);
+}
+
+/// Convert every element of `vals` from native to little-endian byte order.
+pub fn to_le_in_place<T: SwapBytes>(vals: &mut [T]) {
+    convert_in_place(vals, ByteOrder::Little);
+}
+
+/**
+ * Fill `dst` with numbers in the specified byte order from the specified bit
+ * source. This is equivalent to, but much faster than, calling the matching
+ * `read_*` function once per element.
+ *
+ * ```
+ * # use extended_io::{swap::read_slice, ByteOrder};
+ * let mut dst = [0u16; 3];
+ * read_slice(&mut &[0, 1, 0, 2, 1, 0][..], &mut dst, ByteOrder::Big)?;
+ * assert_eq!(dst, [1, 2, 256]);
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ */
+pub fn read_slice<T: SwapBytes>(
+    src: &mut dyn Read,
+    dst: &mut [T],
+    order: ByteOrder,
+) -> io::Result<()> {
+    src.read_exact(as_bytes_mut(dst))?;
+    convert_in_place(dst, order);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn swaps_every_width() {
+        // Odd lengths exercise both the vectorized part and the scalar tail.
+        let mut u16s = (0..37u16).map(|x| x * 0x0102).collect::<Vec<_>>();
+        let expected = u16s.iter().map(|x| x.swap_bytes()).collect::<Vec<_>>();
+        swap_in_place(&mut u16s);
+        assert_eq!(u16s, expected);
+
+        let mut u32s = (0..37u32).map(|x| x * 0x0102_0304).collect::<Vec<_>>();
+        let expected = u32s.iter().map(|x| x.swap_bytes()).collect::<Vec<_>>();
+        swap_in_place(&mut u32s);
+        assert_eq!(u32s, expected);
+
+        let mut u64s = (0..19u64).map(|x| x << 56 | x).collect::<Vec<_>>();
+        let expected = u64s.iter().map(|x| x.swap_bytes()).collect::<Vec<_>>();
+        swap_in_place(&mut u64s);
+        assert_eq!(u64s, expected);
+
+        let mut u128s = (0..5u128).map(|x| x << 120 | x).collect::<Vec<_>>();
+        let expected = u128s.iter().map(|x| x.swap_bytes()).collect::<Vec<_>>();
+        swap_in_place(&mut u128s);
+        assert_eq!(u128s, expected);
+    }
+
+    #[test]
+    fn reads_floats() -> io::Result<()> {
+        let vals = (0..21).map(|x| x as f64 / 4.0).collect::<Vec<_>>();
+        let mut be = Vec::new();
+        let mut le = Vec::new();
+        for val in &vals {
+            crate::write_f64(&mut be, *val)?;
+            crate::write_f64_le(&mut le, *val)?;
+        }
+        let mut dst = vec![0.0; vals.len()];
+        read_slice(&mut &be[..], &mut dst, ByteOrder::Big)?;
+        assert_eq!(dst, vals);
+        read_slice(&mut &le[..], &mut dst, ByteOrder::Little)?;
+        assert_eq!(dst, vals);
+        let err = read_slice(&mut &le[1..], &mut dst, ByteOrder::Little)
+            .expect_err("Read past the end of the source");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        Ok(())
+    }
+}
@@ -0,0 +1,274 @@
+//! Adapters that duplicate a stream of bytes: [`TeeReader`] copies everything
+//! read from a bit source into a bit sink, and [`MultiWriter`] copies
+//! everything written to it into several bit sinks.
+//!
+//! [`TeeReader`]: struct.TeeReader.html
+//! [`MultiWriter`]: struct.MultiWriter.html
+
+use std::io::{self, Read, Write};
+
+/// What to do when one of the bit sinks that a stream is being copied into
+/// fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorPolicy {
+    /// Return the error immediately.
+    FailFast,
+    /// Remember the error, stop copying into the failed sink, and carry on
+    /// with the rest.
+    IgnoreFailed,
+}
+
+/**
+ * A bit source that writes every byte read from it into a second bit sink,
+ * such as a file recording the raw bytes that are being decoded.
+ *
+ * With [`ErrorPolicy::FailFast`], which is the default, an error from the
+ * sink is returned from `read`. The bytes have already been taken from the
+ * source by then, so the read can't be retried. With
+ * [`ErrorPolicy::IgnoreFailed`], the reader keeps working and nothing more is
+ * written to the sink.
+ *
+ * ```
+ * # use extended_io::{self as eio, tee::TeeReader};
+ * let mut raw = Vec::new();
+ * let mut src = TeeReader::new(&b"\x00\x05rest"[..], &mut raw);
+ * assert_eq!(eio::read_u16(&mut src)?, 5);
+ * drop(src);
+ * assert_eq!(raw, b"\x00\x05");
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ *
+ * [`ErrorPolicy::FailFast`]: enum.ErrorPolicy.html#variant.FailFast
+ * [`ErrorPolicy::IgnoreFailed`]: enum.ErrorPolicy.html#variant.IgnoreFailed
+ */
+#[derive(Debug)]
+pub struct TeeReader<R, W> {
+    inner: R,
+    side: W,
+    policy: ErrorPolicy,
+    side_error: Option<io::Error>,
+}
+
+impl<R: Read, W: Write> TeeReader<R, W> {
+    /// Read from `inner`, copying every byte into `side`.
+    pub fn new(inner: R, side: W) -> Self {
+        Self {
+            inner,
+            side,
+            policy: ErrorPolicy::FailFast,
+            side_error: None,
+        }
+    }
+
+    /// Use the specified policy for errors from the sink.
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// The error that made the reader stop copying into the sink, if any.
+    pub fn side_error(&self) -> Option<&io::Error> {
+        self.side_error.as_ref()
+    }
+
+    /// Get a reference to the underlying bit source.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a reference to the sink.
+    pub fn side(&self) -> &W {
+        &self.side
+    }
+
+    /// Unwrap the reader, returning the underlying bit source and the sink.
+    pub fn into_inner(self) -> (R, W) {
+        (self.inner, self.side)
+    }
+}
+
+impl<R: Read, W: Write> Read for TeeReader<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if self.side_error.is_none() {
+            if let Err(e) = self.side.write_all(&buf[..n]) {
+                match self.policy {
+                    ErrorPolicy::FailFast => return Err(e),
+                    ErrorPolicy::IgnoreFailed => self.side_error = Some(e),
+                }
+            }
+        }
+        Ok(n)
+    }
+}
+
+/**
+ * A bit sink that writes everything written to it into each of several other
+ * bit sinks, in order. Sinks of different types can be combined by boxing
+ * them as `Box<dyn Write>`.
+ *
+ * With [`ErrorPolicy::FailFast`], which is the default, the first error from
+ * any sink is returned immediately, in which case the sinks before it have
+ * received the data and the sinks after it haven't. With
+ * [`ErrorPolicy::IgnoreFailed`], a sink that fails is skipped from then on,
+ * and an error is only returned once every sink has failed. Either way,
+ * writing with no sinks left to write to is an error.
+ *
+ * [`ErrorPolicy::FailFast`]: enum.ErrorPolicy.html#variant.FailFast
+ * [`ErrorPolicy::IgnoreFailed`]: enum.ErrorPolicy.html#variant.IgnoreFailed
+ */
+#[derive(Debug)]
+pub struct MultiWriter<W> {
+    sinks: Vec<W>,
+    errors: Vec<Option<io::Error>>,
+    policy: ErrorPolicy,
+}
+
+impl<W: Write> MultiWriter<W> {
+    /// Write to every one of `sinks`.
+    pub fn new(sinks: Vec<W>) -> Self {
+        let errors = sinks.iter().map(|_| None).collect();
+        Self {
+            sinks,
+            errors,
+            policy: ErrorPolicy::FailFast,
+        }
+    }
+
+    /// Use the specified policy for errors from the sinks.
+    pub fn error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Add another sink, which receives only the data written after this call.
+    pub fn push(&mut self, sink: W) {
+        self.sinks.push(sink);
+        self.errors.push(None);
+    }
+
+    /// The error that made the writer stop using the sink at `idx`, if any.
+    pub fn error(&self, idx: usize) -> Option<&io::Error> {
+        self.errors.get(idx)?.as_ref()
+    }
+
+    /// Get references to the sinks.
+    pub fn get_ref(&self) -> &[W] {
+        &self.sinks
+    }
+
+    /// Unwrap the writer, returning the sinks.
+    pub fn into_inner(self) -> Vec<W> {
+        self.sinks
+    }
+
+    /// Apply `op` to every sink that hasn't failed, according to the policy.
+    fn for_each(&mut self, mut op: impl FnMut(&mut W) -> io::Result<()>) -> io::Result<()> {
+        let mut last_error = None;
+        for (sink, error) in self.sinks.iter_mut().zip(&mut self.errors) {
+            if error.is_some() {
+                continue;
+            }
+            if let Err(e) = op(sink) {
+                match self.policy {
+                    ErrorPolicy::FailFast => return Err(e),
+                    ErrorPolicy::IgnoreFailed => {
+                        last_error = Some(io::Error::new(e.kind(), e.to_string()));
+                        *error = Some(e);
+                    }
+                }
+            }
+        }
+        if !self.errors.iter().all(Option::is_some) {
+            return Ok(());
+        }
+        Err(last_error.unwrap_or_else(|| io::Error::other("No sinks left to write to")))
+    }
+}
+
+impl<W: Write> Write for MultiWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.for_each(|sink| sink.write_all(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.for_each(Write::flush)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A sink that accepts `capacity` bytes and then fails.
+    struct Limited {
+        data: Vec<u8>,
+        capacity: usize,
+    }
+
+    impl Write for Limited {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.data.len() + buf.len() > self.capacity {
+                return Err(io::Error::other("Sink is full"));
+            }
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn limited(capacity: usize) -> Limited {
+        Limited {
+            data: Vec::new(),
+            capacity,
+        }
+    }
+
+    #[test]
+    fn tee_reader_policies() -> io::Result<()> {
+        let mut src = TeeReader::new(&b"abcdef"[..], limited(4));
+        let mut buf = [0; 3];
+        src.read_exact(&mut buf)?;
+        assert!(src.read_exact(&mut buf).is_err());
+
+        let mut src =
+            TeeReader::new(&b"abcdef"[..], limited(4)).error_policy(ErrorPolicy::IgnoreFailed);
+        src.read_exact(&mut buf)?;
+        src.read_exact(&mut buf)?;
+        assert_eq!(buf, *b"def");
+        assert!(src.side_error().is_some());
+        assert_eq!(src.into_inner().1.data, b"abc");
+        Ok(())
+    }
+
+    #[test]
+    fn multi_writer_policies() -> io::Result<()> {
+        let mut out = MultiWriter::new(vec![limited(8), limited(2), limited(8)]);
+        out.write_all(b"ab")?;
+        assert!(out.write_all(b"cd").is_err());
+        let sinks = out.into_inner();
+        assert_eq!(sinks[0].data, b"abcd");
+        assert_eq!(sinks[2].data, b"ab");
+
+        let mut out =
+            MultiWriter::new(vec![limited(4), limited(2)]).error_policy(ErrorPolicy::IgnoreFailed);
+        out.write_all(b"abc")?;
+        assert!(out.error(0).is_none());
+        assert!(out.error(1).is_some());
+        out.write_all(b"d")?;
+        assert_eq!(out.get_ref()[0].data, b"abcd");
+        let err = out.write_all(b"e").expect_err("Wrote to failed sinks");
+        assert_eq!(err.to_string(), "Sink is full");
+        out.write_all(b"f")
+            .expect_err("Wrote after every sink failed");
+        out.flush().expect_err("Flushed after every sink failed");
+
+        let mut out = MultiWriter::<Limited>::new(Vec::new());
+        out.write_all(b"a").expect_err("Wrote without any sinks");
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+//! Helpers shared by the tests of several modules.
+
+use std::{
+    cell::Cell,
+    io::{self, Read, Write},
+    time::Duration,
+};
+
+use crate::throttle::Clock;
+
+/// A clock whose time only passes when something sleeps.
+#[derive(Default)]
+pub struct FakeClock(Cell<Duration>);
+
+impl Clock for FakeClock {
+    fn now(&self) -> Duration {
+        self.0.get()
+    }
+
+    fn sleep(&self, dur: Duration) {
+        self.0.set(self.0.get() + dur);
+    }
+}
+
+/// A source that returns at most one byte per read, to exercise code that
+/// has to stitch its input back together.
+pub struct Trickle<'a> {
+    /// The bytes not yet read.
+    pub data: &'a [u8],
+    would_block: bool,
+    blocked: bool,
+}
+
+impl<'a> Trickle<'a> {
+    /// Return the bytes of `data` one at a time.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            would_block: false,
+            blocked: false,
+        }
+    }
+
+    /// Return the bytes of `data` one at a time, with `WouldBlock` before
+    /// each one.
+    pub fn blocking(data: &'a [u8]) -> Self {
+        Self {
+            would_block: true,
+            ..Self::new(data)
+        }
+    }
+}
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.would_block {
+            self.blocked = !self.blocked;
+            if self.blocked {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+        }
+        let n = buf.len().min(self.data.len()).min(1);
+        buf[..n].copy_from_slice(&self.data[..n]);
+        self.data = &self.data[n..];
+        Ok(n)
+    }
+}
+
+/// A sink that accepts at most `max` bytes per write and, while `failing` is
+/// set, fails every other write.
+pub struct Flaky<'a> {
+    /// The bytes written so far.
+    pub data: Vec<u8>,
+    max: usize,
+    failing: &'a Cell<bool>,
+    fail_next: bool,
+}
+
+impl<'a> Flaky<'a> {
+    /// Create a sink that fails while `failing` is set.
+    pub fn new(max: usize, failing: &'a Cell<bool>) -> Self {
+        Self {
+            data: Vec::new(),
+            max,
+            failing,
+            fail_next: false,
+        }
+    }
+}
+
+impl Write for Flaky<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.fail_next = !self.fail_next;
+        if self.failing.get() && self.fail_next {
+            return Err(io::Error::other("Not now"));
+        }
+        let n = buf.len().min(self.max);
+        self.data.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
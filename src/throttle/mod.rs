@@ -0,0 +1,342 @@
+//! Adapters that limit the rate at which bytes pass through a bit source or
+//! bit sink, using a token bucket.
+//!
+//! The bucket holds up to `burst` tokens and is refilled at `rate` tokens per
+//! second. Each byte read or written costs one token, and a read or write that
+//! finds the bucket empty sleeps until a token is available. Time is measured
+//! by a [`Clock`], so tests can substitute one that doesn't really sleep.
+//!
+//! [`Clock`]: trait.Clock.html
+
+use std::{
+    io::{self, Read, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// A source of time for the throttling adapters.
+pub trait Clock {
+    /// The time elapsed since some fixed point, which must never decrease.
+    fn now(&self) -> Duration;
+
+    /// Wait for `dur` to elapse.
+    fn sleep(&self, dur: Duration);
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+
+    fn sleep(&self, dur: Duration) {
+        (**self).sleep(dur)
+    }
+}
+
+/// The real clock, which measures time since its creation and sleeps the
+/// current thread.
+#[derive(Clone, Copy, Debug)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    /// Create a clock that starts counting now.
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep(&self, dur: Duration) {
+        thread::sleep(dur);
+    }
+}
+
+/// A token bucket, with the tokens counted in billionths so that refilling is
+/// exact.
+#[derive(Debug)]
+struct Bucket<C> {
+    rate: u64,
+    burst: u64,
+    nano_tokens: u128,
+    last: Duration,
+    clock: C,
+}
+
+impl<C: Clock> Bucket<C> {
+    fn new(rate: u64, clock: C) -> Self {
+        assert!(rate > 0, "The rate limit must be positive");
+        let last = clock.now();
+        Self {
+            rate,
+            burst: rate,
+            nano_tokens: u128::from(rate) * NANOS_PER_SEC,
+            last,
+            clock,
+        }
+    }
+
+    fn set_burst(&mut self, burst: u64) {
+        assert!(burst > 0, "The burst size must be positive");
+        self.burst = burst;
+        self.nano_tokens = u128::from(burst) * NANOS_PER_SEC;
+    }
+
+    fn with_clock<C2: Clock>(self, clock: C2) -> Bucket<C2> {
+        let last = clock.now();
+        Bucket {
+            rate: self.rate,
+            burst: self.burst,
+            nano_tokens: self.nano_tokens,
+            last,
+            clock,
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_sub(self.last).as_nanos();
+        self.last = now;
+        let max = u128::from(self.burst) * NANOS_PER_SEC;
+        self.nano_tokens = max.min(self.nano_tokens + elapsed * u128::from(self.rate));
+    }
+
+    /// Wait until at least one token is available, then take up to `want`.
+    fn take(&mut self, want: usize) -> usize {
+        loop {
+            self.refill();
+            let tokens = self.nano_tokens / NANOS_PER_SEC;
+            if tokens > 0 {
+                let n = tokens.min(want as u128);
+                self.nano_tokens -= n * NANOS_PER_SEC;
+                return n as usize;
+            }
+            let missing = NANOS_PER_SEC - self.nano_tokens;
+            let wait = missing.div_ceil(u128::from(self.rate));
+            self.clock.sleep(Duration::from_nanos(wait as u64));
+        }
+    }
+
+    /// Return tokens that were taken but not used.
+    fn refund(&mut self, n: usize) {
+        self.nano_tokens += n as u128 * NANOS_PER_SEC;
+    }
+}
+
+/**
+ * A bit source that delivers at most `rate` bytes per second on average,
+ * after an initial burst of up to `burst` bytes. A read that would exceed the
+ * limit returns fewer bytes than requested, sleeping first if not even one
+ * byte is allowed yet.
+ *
+ * ```
+ * # use extended_io::{self as eio, throttle::ThrottledReader};
+ * let mut src = ThrottledReader::new(&b"\x00\x00\x00\x2a"[..], 1024);
+ * assert_eq!(eio::read_u32(&mut src)?, 42);
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ */
+#[derive(Debug)]
+pub struct ThrottledReader<R, C = SystemClock> {
+    inner: R,
+    bucket: Bucket<C>,
+}
+
+impl<R: Read> ThrottledReader<R> {
+    /**
+     * Limit reads from `inner` to `rate` bytes per second, with a burst size
+     * of one second's worth of bytes.
+     *
+     * # Panics
+     * Panics if `rate` is 0.
+     */
+    pub fn new(inner: R, rate: u64) -> Self {
+        Self {
+            inner,
+            bucket: Bucket::new(rate, SystemClock::new()),
+        }
+    }
+}
+
+impl<R: Read, C: Clock> ThrottledReader<R, C> {
+    /**
+     * Allow bursts of up to `burst` bytes, and start with a full bucket.
+     *
+     * # Panics
+     * Panics if `burst` is 0.
+     */
+    pub fn burst(mut self, burst: u64) -> Self {
+        self.bucket.set_burst(burst);
+        self
+    }
+
+    /// Measure time with `clock` instead.
+    pub fn clock<C2: Clock>(self, clock: C2) -> ThrottledReader<R, C2> {
+        ThrottledReader {
+            inner: self.inner,
+            bucket: self.bucket.with_clock(clock),
+        }
+    }
+
+    /// Get a reference to the underlying bit source.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying bit source. Bytes read
+    /// through this reference aren't throttled.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwrap the reader, returning the underlying bit source.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read, C: Clock> Read for ThrottledReader<R, C> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let allowed = self.bucket.take(buf.len());
+        let result = self.inner.read(&mut buf[..allowed]);
+        self.bucket.refund(allowed - *result.as_ref().unwrap_or(&0));
+        result
+    }
+}
+
+/**
+ * A bit sink that accepts at most `rate` bytes per second on average, after
+ * an initial burst of up to `burst` bytes. A write that would exceed the limit
+ * accepts fewer bytes than offered, sleeping first if not even one byte is
+ * allowed yet.
+ */
+#[derive(Debug)]
+pub struct ThrottledWriter<W, C = SystemClock> {
+    inner: W,
+    bucket: Bucket<C>,
+}
+
+impl<W: Write> ThrottledWriter<W> {
+    /**
+     * Limit writes to `inner` to `rate` bytes per second, with a burst size
+     * of one second's worth of bytes.
+     *
+     * # Panics
+     * Panics if `rate` is 0.
+     */
+    pub fn new(inner: W, rate: u64) -> Self {
+        Self {
+            inner,
+            bucket: Bucket::new(rate, SystemClock::new()),
+        }
+    }
+}
+
+impl<W: Write, C: Clock> ThrottledWriter<W, C> {
+    /**
+     * Allow bursts of up to `burst` bytes, and start with a full bucket.
+     *
+     * # Panics
+     * Panics if `burst` is 0.
+     */
+    pub fn burst(mut self, burst: u64) -> Self {
+        self.bucket.set_burst(burst);
+        self
+    }
+
+    /// Measure time with `clock` instead.
+    pub fn clock<C2: Clock>(self, clock: C2) -> ThrottledWriter<W, C2> {
+        ThrottledWriter {
+            inner: self.inner,
+            bucket: self.bucket.with_clock(clock),
+        }
+    }
+
+    /// Get a reference to the underlying bit sink.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying bit sink. Bytes written
+    /// through this reference aren't throttled.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwrap the writer, returning the underlying bit sink.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write, C: Clock> Write for ThrottledWriter<W, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let allowed = self.bucket.take(buf.len());
+        let result = self.inner.write(&buf[..allowed]);
+        self.bucket.refund(allowed - *result.as_ref().unwrap_or(&0));
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{pipe::mk_pipe, test_util::FakeClock};
+
+    #[test]
+    fn reads_at_the_limit() -> io::Result<()> {
+        let clock = FakeClock::default();
+        let data = vec![7; 1000];
+        let mut src = ThrottledReader::new(&data[..], 100).burst(10).clock(&clock);
+        let mut buf = [0; 64];
+        assert_eq!(src.read(&mut buf)?, 10);
+        assert_eq!(clock.now(), Duration::ZERO);
+        assert_eq!(src.read(&mut buf)?, 1);
+        assert_eq!(clock.now(), Duration::from_millis(10));
+
+        let mut rest = [0; 989];
+        src.read_exact(&mut rest)?;
+        assert_eq!(clock.now(), Duration::from_millis(9900));
+        Ok(())
+    }
+
+    #[test]
+    fn writes_into_a_pipe() -> io::Result<()> {
+        let clock = FakeClock::default();
+        let (mut read, write) = mk_pipe();
+        let mut out = ThrottledWriter::new(write, 4).clock(&clock);
+        for x in 0..3 {
+            crate::write_u32(&mut out, x)?;
+        }
+        assert_eq!(clock.now(), Duration::from_secs(2));
+        for x in 0..3 {
+            assert_eq!(crate::read_u32(&mut read)?, x);
+        }
+        Ok(())
+    }
+}
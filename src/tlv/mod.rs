@@ -0,0 +1,447 @@
+//! Readers and writers for type-length-value records, in which each record is
+//! a tag and a length, each encoded as an [`IntEncoding`], followed by that
+//! many bytes of value.
+//!
+//! A [`TlvFormat`] describes the layout of the records, and can iterate over
+//! the records in a slice without copying them. A [`TlvReader`] reads records
+//! from a bit source, and a [`TlvWriter`] writes them to a bit sink.
+//!
+//! ```
+//! # use extended_io::{tlv::{LengthCovers, TlvFormat}, IntEncoding};
+//! // Bluetooth advertising data puts the length first, and counts the tag.
+//! let format = TlvFormat::new(IntEncoding::U8, IntEncoding::U8)
+//!     .length_first()
+//!     .length_covers(LengthCovers::TagAndValue);
+//! let data = b"\x02\x01\x06\x05\x09abcd";
+//! let records = format.records(data).collect::<std::io::Result<Vec<_>>>()?;
+//! assert_eq!(records, [(0x01, &b"\x06"[..]), (0x09, &b"abcd"[..])]);
+//! # Ok::<(), std::io::Error>(())
+//! ```
+//!
+//! [`IntEncoding`]: ../enum.IntEncoding.html
+//! [`TlvFormat`]: struct.TlvFormat.html
+//! [`TlvReader`]: struct.TlvReader.html
+//! [`TlvWriter`]: struct.TlvWriter.html
+
+use std::io::{self, Error, Read, Write};
+
+use crate::{frame::DEFAULT_MAX_FRAME_SIZE, IntEncoding};
+
+/// Which parts of a record its length counts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LengthCovers {
+    /// Only the value.
+    Value,
+    /// The tag and the value, but not the length itself.
+    TagAndValue,
+    /// The whole record, header included.
+    Record,
+}
+
+/**
+ * The layout of a type-length-value record: the encodings of its tag and
+ * length, which of them comes first, and what the length counts.
+ *
+ * By default the tag comes first, the length counts only the value, and
+ * values may be up to [`DEFAULT_MAX_FRAME_SIZE`] bytes long.
+ *
+ * [`DEFAULT_MAX_FRAME_SIZE`]: ../frame/constant.DEFAULT_MAX_FRAME_SIZE.html
+ */
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TlvFormat {
+    tag: IntEncoding,
+    length: IntEncoding,
+    length_first: bool,
+    covers: LengthCovers,
+    max_len: usize,
+}
+
+impl TlvFormat {
+    /// Describe records whose tags and lengths are encoded as `tag` and
+    /// `length`.
+    pub fn new(tag: IntEncoding, length: IntEncoding) -> Self {
+        Self {
+            tag,
+            length,
+            length_first: false,
+            covers: LengthCovers::Value,
+            max_len: DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+
+    /// Put the length before the tag.
+    pub fn length_first(mut self) -> Self {
+        self.length_first = true;
+        self
+    }
+
+    /// Make the length count `covers` instead of just the value.
+    pub fn length_covers(mut self, covers: LengthCovers) -> Self {
+        self.covers = covers;
+        self
+    }
+
+    /// Reject values longer than `max` bytes, instead of the default of
+    /// [`DEFAULT_MAX_FRAME_SIZE`].
+    ///
+    /// [`DEFAULT_MAX_FRAME_SIZE`]: ../frame/constant.DEFAULT_MAX_FRAME_SIZE.html
+    pub fn max_len(mut self, max: usize) -> Self {
+        self.max_len = max;
+        self
+    }
+
+    /// Iterate over the records in `bytes`, borrowing their values. A
+    /// malformed or truncated record produces an error, after which the
+    /// iterator ends.
+    pub fn records(self, bytes: &[u8]) -> TlvRecords<'_> {
+        TlvRecords {
+            bytes,
+            format: self,
+        }
+    }
+
+    /// The encodings of the two header fields, in the order they're stored.
+    fn fields(&self) -> [IntEncoding; 2] {
+        if self.length_first {
+            [self.length, self.tag]
+        } else {
+            [self.tag, self.length]
+        }
+    }
+
+    /// Split the two header fields, in the order they're stored, into the tag
+    /// and the length of the value.
+    fn split(&self, fields: [(u64, usize); 2]) -> io::Result<(u64, usize)> {
+        let [(tag, tag_len), (len, len_len)] = if self.length_first {
+            [fields[1], fields[0]]
+        } else {
+            fields
+        };
+        let header = match self.covers {
+            LengthCovers::Value => 0,
+            LengthCovers::TagAndValue => tag_len,
+            LengthCovers::Record => tag_len + len_len,
+        };
+        let value_len = len.checked_sub(header as u64).ok_or_else(|| {
+            let msg = format!("Record length {} is shorter than its header", len);
+            Error::new(io::ErrorKind::InvalidData, msg)
+        })?;
+        if value_len > self.max_len as u64 {
+            let msg = format!(
+                "Record value of {} bytes exceeds the maximum of {}",
+                value_len, self.max_len,
+            );
+            return Err(Error::new(io::ErrorKind::InvalidData, msg));
+        }
+        Ok((tag, value_len as usize))
+    }
+
+    /// Append the header of a record with the specified tag and value length
+    /// to `out`.
+    fn encode_header(&self, tag: u64, value_len: usize, out: &mut Vec<u8>) -> io::Result<()> {
+        if value_len > self.max_len {
+            let msg = format!(
+                "Record value of {} bytes exceeds the maximum of {}",
+                value_len, self.max_len,
+            );
+            return Err(Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+        let tag_len = self.tag.encoded_len(tag) as u64;
+        let mut len = value_len as u64;
+        match self.covers {
+            LengthCovers::Value => {}
+            LengthCovers::TagAndValue => len += tag_len,
+            LengthCovers::Record => {
+                len += tag_len;
+                // A varint length can grow when its own length is added.
+                let mut len_len = self.length.encoded_len(len);
+                while self.length.encoded_len(len + len_len as u64) != len_len {
+                    len_len += 1;
+                }
+                len += len_len as u64;
+            }
+        }
+        if self.length_first {
+            self.length.write(out, len)?;
+            self.tag.write(out, tag)
+        } else {
+            self.tag.write(out, tag)?;
+            self.length.write(out, len)
+        }
+    }
+}
+
+/// An iterator over the records in a slice, created by
+/// [`TlvFormat::records`].
+///
+/// [`TlvFormat::records`]: struct.TlvFormat.html#method.records
+#[derive(Clone, Debug)]
+pub struct TlvRecords<'a> {
+    bytes: &'a [u8],
+    format: TlvFormat,
+}
+
+impl<'a> TlvRecords<'a> {
+    /// The bytes after the records returned so far.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    fn next_record(&mut self) -> io::Result<(u64, &'a [u8])> {
+        let mut fields = [(0, 0); 2];
+        let mut pos = 0;
+        for (field, encoding) in fields.iter_mut().zip(&self.format.fields()) {
+            *field = encoding.decode(&self.bytes[pos..])?.ok_or_else(|| {
+                Error::new(io::ErrorKind::UnexpectedEof, "Record header cut short")
+            })?;
+            pos += field.1;
+        }
+        let (tag, value_len) = self.format.split(fields)?;
+        let value = self.bytes[pos..].get(..value_len).ok_or_else(|| {
+            let msg = format!("Record value cut short: expected {} bytes", value_len);
+            Error::new(io::ErrorKind::UnexpectedEof, msg)
+        })?;
+        self.bytes = &self.bytes[pos + value_len..];
+        Ok((tag, value))
+    }
+}
+
+impl<'a> Iterator for TlvRecords<'a> {
+    type Item = io::Result<(u64, &'a [u8])>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+        let record = self.next_record();
+        if record.is_err() {
+            self.bytes = &[];
+        }
+        Some(record)
+    }
+}
+
+/**
+ * A bit source of type-length-value records. It's also an iterator over the
+ * records, which ends after the first error.
+ *
+ * Only the bytes of each record are read, so other data can follow the
+ * records in the same bit source.
+ *
+ * ```
+ * # use extended_io::{tlv::{TlvFormat, TlvReader, TlvWriter}, ByteOrder, IntEncoding};
+ * let format = TlvFormat::new(IntEncoding::U16(ByteOrder::Little), IntEncoding::Varint);
+ * let mut out = TlvWriter::new(Vec::new(), format);
+ * out.write_record(7, b"seven")?;
+ * out.write_record(1000, b"")?;
+ * let data = out.into_inner();
+ * assert_eq!(data, b"\x07\x00\x05seven\xe8\x03\x00");
+ *
+ * let records = TlvReader::new(&data[..], format).collect::<std::io::Result<Vec<_>>>()?;
+ * assert_eq!(records, [(7, b"seven".to_vec()), (1000, Vec::new())]);
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ */
+#[derive(Debug)]
+pub struct TlvReader<R> {
+    inner: R,
+    format: TlvFormat,
+    header: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> TlvReader<R> {
+    /// Read records laid out as `format` from `inner`.
+    pub fn new(inner: R, format: TlvFormat) -> Self {
+        Self {
+            inner,
+            format,
+            header: Vec::with_capacity(20),
+            done: false,
+        }
+    }
+
+    /// Get a reference to the underlying bit source.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying bit source.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwrap the reader, returning the underlying bit source.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /**
+     * Read the tag and value of the next record, or `None` if the bit source
+     * is exhausted before the record starts.
+     *
+     * Returns an error of kind `UnexpectedEof` if the bit source is exhausted
+     * partway through the record, and of kind `InvalidData` if its length is
+     * malformed or its value is too long.
+     */
+    pub fn read_record(&mut self) -> io::Result<Option<(u64, Vec<u8>)>> {
+        self.header.clear();
+        let mut first = [0; 1];
+        loop {
+            match self.inner.read(&mut first) {
+                Ok(0) => return Ok(None),
+                Ok(_) => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.header.push(first[0]);
+        let mut fields = [(0, 0); 2];
+        let mut pos = 0;
+        for (field, encoding) in fields.iter_mut().zip(&self.format.fields()) {
+            *field = loop {
+                if let Some(field) = encoding.decode(&self.header[pos..])? {
+                    break field;
+                }
+                self.header.push(crate::read_u8(&mut self.inner)?);
+            };
+            pos += field.1;
+        }
+        let (tag, value_len) = self.format.split(fields)?;
+        let mut value = vec![0; value_len];
+        self.inner.read_exact(&mut value)?;
+        Ok(Some((tag, value)))
+    }
+}
+
+impl<R: Read> Iterator for TlvReader<R> {
+    type Item = io::Result<(u64, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let record = self.read_record().transpose();
+        if !matches!(record, Some(Ok(_))) {
+            self.done = true;
+        }
+        record
+    }
+}
+
+/// A bit sink of type-length-value records, the counterpart of
+/// [`TlvReader`].
+///
+/// [`TlvReader`]: struct.TlvReader.html
+#[derive(Debug)]
+pub struct TlvWriter<W> {
+    inner: W,
+    format: TlvFormat,
+    header: Vec<u8>,
+}
+
+impl<W: Write> TlvWriter<W> {
+    /// Write records laid out as `format` to `inner`.
+    pub fn new(inner: W, format: TlvFormat) -> Self {
+        Self {
+            inner,
+            format,
+            header: Vec::with_capacity(20),
+        }
+    }
+
+    /// Get a reference to the underlying bit sink.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying bit sink.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwrap the writer, returning the underlying bit sink.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Write a record with the specified tag and value. Returns an error of
+    /// kind `InvalidInput`, before writing anything, if the value is too long
+    /// or the tag or length doesn't fit in its encoding.
+    pub fn write_record(&mut self, tag: u64, value: &[u8]) -> io::Result<()> {
+        self.header.clear();
+        self.format
+            .encode_header(tag, value.len(), &mut self.header)?;
+        self.inner.write_all(&self.header)?;
+        self.inner.write_all(value)
+    }
+
+    /// Flush the underlying bit sink.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ByteOrder;
+
+    #[test]
+    fn round_trips_records() -> io::Result<()> {
+        let records = [(1, vec![0xAA; 3]), (300, Vec::new()), (70000, vec![5; 200])];
+        let formats = [
+            TlvFormat::new(IntEncoding::U32(ByteOrder::Big), IntEncoding::U8),
+            TlvFormat::new(IntEncoding::Varint, IntEncoding::U16(ByteOrder::Little))
+                .length_first()
+                .length_covers(LengthCovers::TagAndValue),
+            TlvFormat::new(IntEncoding::Varint, IntEncoding::Varint)
+                .length_covers(LengthCovers::Record),
+            TlvFormat::new(IntEncoding::U32(ByteOrder::Native), IntEncoding::Varint)
+                .length_first()
+                .length_covers(LengthCovers::Record),
+        ];
+        for &format in &formats {
+            let mut out = TlvWriter::new(Vec::new(), format);
+            for (tag, value) in &records {
+                out.write_record(*tag, value)?;
+            }
+            let data = out.into_inner();
+            let read = TlvReader::new(&data[..], format).collect::<io::Result<Vec<_>>>()?;
+            assert_eq!(read, records, "{:?}", format);
+            let borrowed = format.records(&data).collect::<io::Result<Vec<_>>>()?;
+            assert!(borrowed
+                .iter()
+                .map(|&(tag, value)| (tag, value.to_vec()))
+                .eq(records.iter().cloned()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_malformed_records() {
+        let format = TlvFormat::new(IntEncoding::U8, IntEncoding::U8).max_len(4);
+        let covered = format.length_covers(LengthCovers::Record);
+        for (format, data, kind) in [
+            (format, &b"\x01\x05abcde"[..], io::ErrorKind::InvalidData),
+            (covered, b"\x01\x01", io::ErrorKind::InvalidData),
+            (format, b"\x01\x03ab", io::ErrorKind::UnexpectedEof),
+            (format, b"\x01", io::ErrorKind::UnexpectedEof),
+        ] {
+            let mut records = format.records(data);
+            let err = records.next().unwrap().expect_err("Accepted a bad record");
+            assert_eq!(err.kind(), kind, "{:?}", data);
+            assert!(records.next().is_none());
+            let mut src = TlvReader::new(data, format);
+            let err = src.next().unwrap().expect_err("Read a bad record");
+            assert_eq!(err.kind(), kind, "{:?}", data);
+            assert!(src.next().is_none());
+        }
+        let mut out = TlvWriter::new(Vec::new(), format);
+        let err = out
+            .write_record(256, b"")
+            .expect_err("Wrote an unencodable tag");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(out.into_inner().is_empty());
+    }
+}
@@ -0,0 +1,338 @@
+//! A debugging wrapper around a bit source that reports every value decoded
+//! from it, for dissecting a binary format one field at a time.
+
+use std::{
+    fmt,
+    io::{self, Read, Write},
+};
+
+/// A record of one value decoded by a [`TracingReader`].
+///
+/// [`TracingReader`]: struct.TracingReader.html
+#[derive(Clone, Copy, Debug)]
+pub struct TraceEvent<'a> {
+    /// The offset of the first byte of the value from the start of the trace.
+    pub offset: u64,
+    /// The name of the type that was decoded, such as `u32le`, or `raw` for
+    /// bytes read directly through the `Read` implementation.
+    pub ty: &'a str,
+    /// The bytes that were consumed.
+    pub raw: &'a [u8],
+    /// The decoded value, or a description of the error that stopped it from
+    /// being decoded.
+    pub value: &'a str,
+}
+
+impl fmt::Display for TraceEvent<'_> {
+    /**
+     * Format the event as a line of a dissection, like
+     *
+     * ```text
+     * 00000004  u16le    02 00                    2
+     * ```
+     */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = self
+            .raw
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(" ");
+        write!(
+            f,
+            "{:08x}  {:<7}  {:<23}  {}",
+            self.offset, self.ty, hex, self.value
+        )
+    }
+}
+
+enum Sink<'a> {
+    Writer(Box<dyn Write + 'a>),
+    Callback(Box<dyn FnMut(&TraceEvent<'_>) + 'a>),
+}
+
+/// Records every byte read through it on behalf of a traced decode.
+struct Capture<'r, R> {
+    inner: &'r mut R,
+    raw: &'r mut Vec<u8>,
+}
+
+impl<R: Read> Read for Capture<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.raw.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/**
+ * A bit source that reports each value decoded from it to a bit sink or a
+ * callback. Values decoded with [`trace`] or one of the `read_*` methods are
+ * reported with their type and value. Reads made directly through the `Read`
+ * implementation, including those made by the crate's free `read_*`
+ * functions, are each reported as `raw` along with their ASCII rendering.
+ *
+ * When a sink is used, each event is written to it on its own line in the
+ * format of [`TraceEvent`]'s `Display` implementation. Errors writing to the
+ * sink are ignored so that they can't interfere with the decoding being
+ * traced.
+ *
+ * [`trace`]: #method.trace
+ * [`TraceEvent`]: struct.TraceEvent.html
+ */
+pub struct TracingReader<'a, R> {
+    inner: R,
+    offset: u64,
+    sink: Sink<'a>,
+    raw: Vec<u8>,
+}
+
+macro_rules! traced {
+    ($($name:ident -> $t:ty: $ty:literal;)*) => {
+        $(
+            #[doc = concat!(
+                "Trace a call to [`", stringify!($name), "`](../fn.",
+                stringify!($name), ".html).",
+            )]
+            pub fn $name(&mut self) -> io::Result<$t> {
+                self.trace($ty, crate::$name)
+            }
+        )*
+    };
+}
+
+impl<'a, R: Read> TracingReader<'a, R> {
+    /// Trace the reads from `inner`, writing the trace to `sink`.
+    pub fn new(inner: R, sink: impl Write + 'a) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            sink: Sink::Writer(Box::new(sink)),
+            raw: Vec::new(),
+        }
+    }
+
+    /// Trace the reads from `inner`, passing each event to `callback`.
+    pub fn with_callback(inner: R, callback: impl FnMut(&TraceEvent<'_>) + 'a) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            sink: Sink::Callback(Box::new(callback)),
+            raw: Vec::new(),
+        }
+    }
+
+    /// The number of bytes read so far.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Get a reference to the underlying bit source.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the underlying bit source. Bytes read
+    /// through this reference are neither traced nor counted.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwrap the tracer, returning the underlying bit source.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /**
+     * Decode a value with `read` and report it as a `ty`, along with the bytes
+     * that `read` consumed. A failed decode is reported too, with the error in
+     * place of the value, before the error is returned.
+     *
+     * ```
+     * # use extended_io::{self as eio, trace::TracingReader};
+     * let mut trace = Vec::new();
+     * let mut src = TracingReader::new(&b"\x00\x03abc"[..], &mut trace);
+     * let len = src.trace("len", eio::read_u16)?;
+     * src.trace("name", |src| eio::read_bytes(src, len.into()))?;
+     * drop(src);
+     * assert_eq!(
+     *     String::from_utf8(trace).unwrap(),
+     *     "00000000  len      00 03                    3\n\
+     *      00000002  name     61 62 63                 [97, 98, 99]\n",
+     * );
+     * # Ok::<(), std::io::Error>(())
+     * ```
+     */
+    pub fn trace<T, F>(&mut self, ty: &str, read: F) -> io::Result<T>
+    where
+        T: fmt::Debug,
+        F: FnOnce(&mut dyn Read) -> io::Result<T>,
+    {
+        self.raw.clear();
+        let result = read(&mut Capture {
+            inner: &mut self.inner,
+            raw: &mut self.raw,
+        });
+        let value = match &result {
+            Ok(x) => format!("{:?}", x),
+            Err(e) => format!("error: {}", e),
+        };
+        self.emit(ty, &value);
+        result
+    }
+
+    fn emit(&mut self, ty: &str, value: &str) {
+        let event = TraceEvent {
+            offset: self.offset,
+            ty,
+            raw: &self.raw,
+            value,
+        };
+        match &mut self.sink {
+            Sink::Writer(out) => {
+                let _ = writeln!(out, "{}", event);
+            }
+            Sink::Callback(callback) => callback(&event),
+        }
+        self.offset += self.raw.len() as u64;
+    }
+
+    /// Trace a call to [`read_bytes`](../fn.read_bytes.html).
+    pub fn read_bytes(&mut self, length: u64) -> io::Result<Vec<u8>> {
+        self.trace("bytes", |src| crate::read_bytes(src, length))
+    }
+
+    traced! {
+        read_u8 -> u8: "u8";
+        read_u8_le -> u8: "u8";
+        read_u8_ne -> u8: "u8";
+        read_i8 -> i8: "i8";
+        read_i8_le -> i8: "i8";
+        read_i8_ne -> i8: "i8";
+        read_u16 -> u16: "u16be";
+        read_u16_le -> u16: "u16le";
+        read_u16_ne -> u16: "u16ne";
+        read_i16 -> i16: "i16be";
+        read_i16_le -> i16: "i16le";
+        read_i16_ne -> i16: "i16ne";
+        read_u32 -> u32: "u32be";
+        read_u32_le -> u32: "u32le";
+        read_u32_ne -> u32: "u32ne";
+        read_i32 -> i32: "i32be";
+        read_i32_le -> i32: "i32le";
+        read_i32_ne -> i32: "i32ne";
+        read_u64 -> u64: "u64be";
+        read_u64_le -> u64: "u64le";
+        read_u64_ne -> u64: "u64ne";
+        read_i64 -> i64: "i64be";
+        read_i64_le -> i64: "i64le";
+        read_i64_ne -> i64: "i64ne";
+        read_u128 -> u128: "u128be";
+        read_u128_le -> u128: "u128le";
+        read_u128_ne -> u128: "u128ne";
+        read_i128 -> i128: "i128be";
+        read_i128_le -> i128: "i128le";
+        read_i128_ne -> i128: "i128ne";
+        read_f32 -> f32: "f32be";
+        read_f32_le -> f32: "f32le";
+        read_f32_ne -> f32: "f32ne";
+        read_f64 -> f64: "f64be";
+        read_f64_le -> f64: "f64le";
+        read_f64_ne -> f64: "f64ne";
+    }
+}
+
+impl<R: Read> Read for TracingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            self.raw.clear();
+            self.raw.extend_from_slice(&buf[..n]);
+            let ascii = buf[..n]
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<String>();
+            self.emit("raw", &ascii);
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::read_u16;
+
+    #[test]
+    fn writes_a_dissection() -> io::Result<()> {
+        let mut trace = Vec::new();
+        let mut src = TracingReader::new(
+            &b"\xff\xfe\x01\x00\x00\x00\x00\x00\x80?AB\n"[..],
+            &mut trace,
+        );
+        assert_eq!(src.read_i16()?, -2);
+        assert_eq!(src.read_u32_le()?, 1);
+        assert_eq!(src.read_f32_le()?, 1.0);
+        assert_eq!(read_u16(&mut src)?, 0x4142);
+        assert!(src.read_u16().is_err());
+        assert_eq!(src.offset(), 13);
+        drop(src);
+        assert_eq!(
+            String::from_utf8(trace).unwrap(),
+            "00000000  i16be    ff fe                    -2\n\
+             00000002  u32le    01 00 00 00              1\n\
+             00000006  f32le    00 00 80 3f              1.0\n\
+             0000000a  raw      41 42                    AB\n\
+             0000000c  u16be    0a                       error: failed to fill whole buffer\n",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn passes_events_to_callback() -> io::Result<()> {
+        let mut events = Vec::new();
+        let mut src = TracingReader::with_callback(&[1u8, 2, 3][..], |event| {
+            events.push((event.offset, event.ty.to_string(), event.raw.to_vec()));
+        });
+        src.read_u8()?;
+        src.read_bytes(2)?;
+        drop(src);
+        assert_eq!(
+            events,
+            vec![
+                (0, "u8".to_string(), vec![1]),
+                (1, "bytes".to_string(), vec![2, 3]),
+            ],
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn types_every_primitive() -> io::Result<()> {
+        let mut types = Vec::new();
+        let mut src = TracingReader::with_callback(&[0; 512][..], |event| {
+            types.push(event.ty.to_string());
+        });
+        macro_rules! read_all {
+            ($($read:ident)*) => { $(src.$read()?;)* };
+        }
+        read_all! {
+            read_u8 read_u8_le read_u8_ne read_i8 read_i8_le read_i8_ne
+            read_u16 read_u16_le read_u16_ne read_i16 read_i16_le read_i16_ne
+            read_u32 read_u32_le read_u32_ne read_i32 read_i32_le read_i32_ne
+            read_u64 read_u64_le read_u64_ne read_i64 read_i64_le read_i64_ne
+            read_u128 read_u128_le read_u128_ne read_i128 read_i128_le read_i128_ne
+            read_f32 read_f32_le read_f32_ne read_f64 read_f64_le read_f64_ne
+        }
+        drop(src);
+        assert_eq!(types.len(), 36);
+        assert!(!types.contains(&"raw".to_string()), "{:?}", types);
+        Ok(())
+    }
+}
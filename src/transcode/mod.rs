@@ -0,0 +1,461 @@
+//! A reader that converts text in a legacy or UTF-16/32 encoding into UTF-8
+//! as it's read, so that the rest of the crate's text functions can be used on
+//! it.
+
+use std::{
+    fmt,
+    io::{self, BufRead, Error, Read},
+};
+
+const CHUNK: usize = 8 * 1024;
+
+/// An encoding that [`TranscodingReader`] can convert from.
+///
+/// [`TranscodingReader`]: struct.TranscodingReader.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// UTF-8, which is validated and passed through.
+    Utf8,
+    /// ISO 8859-1, which maps each byte to the code point of the same value.
+    Latin1,
+    /// Windows code page 1252, a superset of the printable part of Latin-1.
+    Windows1252,
+    /// IBM code page 437, the character set of the original IBM PC. Bytes
+    /// below 0x80 are treated as ASCII rather than as the PC's graphical
+    /// symbols.
+    Cp437,
+    /// Little-endian UTF-16.
+    Utf16Le,
+    /// Big-endian UTF-16.
+    Utf16Be,
+    /// Little-endian UTF-32.
+    Utf32Le,
+    /// Big-endian UTF-32.
+    Utf32Be,
+}
+
+impl Encoding {
+    /**
+     * Identify the encoding of `bytes` by its byte order mark, returning the
+     * encoding and the length of the mark. A UTF-32LE mark is recognized in
+     * preference to the UTF-16LE mark that it begins with.
+     */
+    pub fn from_bom(bytes: &[u8]) -> Option<(Self, usize)> {
+        match bytes {
+            [0xEF, 0xBB, 0xBF, ..] => Some((Self::Utf8, 3)),
+            [0xFF, 0xFE, 0, 0, ..] => Some((Self::Utf32Le, 4)),
+            [0, 0, 0xFE, 0xFF, ..] => Some((Self::Utf32Be, 4)),
+            [0xFF, 0xFE, ..] => Some((Self::Utf16Le, 2)),
+            [0xFE, 0xFF, ..] => Some((Self::Utf16Be, 2)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Utf8 => "UTF-8",
+            Self::Latin1 => "Latin-1",
+            Self::Windows1252 => "Windows-1252",
+            Self::Cp437 => "CP437",
+            Self::Utf16Le => "UTF-16LE",
+            Self::Utf16Be => "UTF-16BE",
+            Self::Utf32Le => "UTF-32LE",
+            Self::Utf32Be => "UTF-32BE",
+        })
+    }
+}
+
+/// What to do with input that isn't valid in its encoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorMode {
+    /// Report an error of kind `InvalidData`.
+    Strict,
+    /// Replace the invalid input with U+FFFD REPLACEMENT CHARACTER.
+    Lossy,
+}
+
+/// The characters of bytes 0x80 through 0xFF in code page 437.
+#[rustfmt::skip]
+const CP437: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{a0}',
+];
+
+/// The characters of bytes 0x80 through 0x9F in code page 1252, which differ
+/// from Latin-1. Five of the bytes are undefined.
+#[rustfmt::skip]
+const WINDOWS_1252: [Option<char>; 32] = [
+    Some('€'), None, Some('‚'), Some('ƒ'), Some('„'), Some('…'), Some('†'), Some('‡'),
+    Some('ˆ'), Some('‰'), Some('Š'), Some('‹'), Some('Œ'), None, Some('Ž'), None,
+    None, Some('‘'), Some('’'), Some('“'), Some('”'), Some('•'), Some('–'), Some('—'),
+    Some('˜'), Some('™'), Some('š'), Some('›'), Some('œ'), None, Some('ž'), Some('Ÿ'),
+];
+
+/// The result of decoding the start of some input.
+enum Step {
+    /// A character encoded in this many bytes.
+    Char(char, usize),
+    /// This many bytes of invalid input.
+    Invalid(usize),
+    /// Not enough input to tell.
+    Incomplete,
+}
+
+/// Decode the first character of `bytes`, which is non-empty.
+fn decode_one(encoding: Encoding, bytes: &[u8], eof: bool) -> Step {
+    // Input that's cut short at the end of the source is invalid.
+    let incomplete = if eof {
+        Step::Invalid(bytes.len())
+    } else {
+        Step::Incomplete
+    };
+    match encoding {
+        Encoding::Latin1 => Step::Char(bytes[0].into(), 1),
+        Encoding::Windows1252 => match bytes[0] {
+            b @ 0x80..=0x9F => match WINDOWS_1252[usize::from(b - 0x80)] {
+                Some(c) => Step::Char(c, 1),
+                None => Step::Invalid(1),
+            },
+            b => Step::Char(b.into(), 1),
+        },
+        Encoding::Cp437 => match bytes[0] {
+            b @ 0x80..=0xFF => Step::Char(CP437[usize::from(b - 0x80)], 1),
+            b => Step::Char(b.into(), 1),
+        },
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let unit = |idx: usize| {
+                let pair = [bytes[idx], bytes[idx + 1]];
+                if encoding == Encoding::Utf16Le {
+                    u16::from_le_bytes(pair)
+                } else {
+                    u16::from_be_bytes(pair)
+                }
+            };
+            if bytes.len() < 2 {
+                return incomplete;
+            }
+            match unit(0) {
+                high @ 0xD800..=0xDBFF => {
+                    if bytes.len() < 4 {
+                        return if eof { Step::Invalid(2) } else { incomplete };
+                    }
+                    match unit(2) {
+                        low @ 0xDC00..=0xDFFF => {
+                            let c = 0x10000
+                                + ((u32::from(high) - 0xD800) << 10)
+                                + (u32::from(low) - 0xDC00);
+                            Step::Char(char::from_u32(c).unwrap(), 4)
+                        }
+                        _ => Step::Invalid(2),
+                    }
+                }
+                0xDC00..=0xDFFF => Step::Invalid(2),
+                c => Step::Char(char::from_u32(c.into()).unwrap(), 2),
+            }
+        }
+        Encoding::Utf32Le | Encoding::Utf32Be => {
+            if bytes.len() < 4 {
+                return incomplete;
+            }
+            let quad = [bytes[0], bytes[1], bytes[2], bytes[3]];
+            let c = if encoding == Encoding::Utf32Le {
+                u32::from_le_bytes(quad)
+            } else {
+                u32::from_be_bytes(quad)
+            };
+            match char::from_u32(c) {
+                Some(c) => Step::Char(c, 4),
+                None => Step::Invalid(4),
+            }
+        }
+        Encoding::Utf8 => {
+            let prefix = &bytes[..bytes.len().min(4)];
+            let valid = match std::str::from_utf8(prefix) {
+                Ok(s) => s,
+                Err(e) if e.valid_up_to() > 0 => {
+                    std::str::from_utf8(&prefix[..e.valid_up_to()]).unwrap()
+                }
+                Err(e) => {
+                    return match e.error_len() {
+                        Some(len) => Step::Invalid(len),
+                        None => incomplete,
+                    }
+                }
+            };
+            let c = valid.chars().next().unwrap();
+            Step::Char(c, c.len_utf8())
+        }
+    }
+}
+
+/**
+ * A buffered bit source that converts text from the underlying bit source
+ * into UTF-8. The encoding can be given explicitly with [`new`] or detected
+ * from a byte order mark with [`sniff`]. Since it implements `BufRead`, it
+ * can be passed straight to [`read_t`], [`parse_lines`], or a [`Scanner`].
+ *
+ * In [`ErrorMode::Strict`], which is the default, invalid input produces an
+ * error of kind `InvalidData` once the text before it has been read. The
+ * invalid bytes are then skipped, so reading can continue. In
+ * [`ErrorMode::Lossy`], each invalid sequence becomes U+FFFD instead.
+ *
+ * ```
+ * # use extended_io::transcode::{Encoding, TranscodingReader};
+ * let src = &b"\xff\xfe4\x002\x00\n\x00"[..];
+ * let mut src = TranscodingReader::sniff(src, Encoding::Windows1252);
+ * assert_eq!(extended_io::read_t::<u32>(&mut src)?, 42);
+ * assert_eq!(src.encoding(), Some(Encoding::Utf16Le));
+ * # Ok::<(), std::io::Error>(())
+ * ```
+ *
+ * [`new`]: #method.new
+ * [`sniff`]: #method.sniff
+ * [`read_t`]: ../fn.read_t.html
+ * [`parse_lines`]: ../lines/fn.parse_lines.html
+ * [`Scanner`]: ../scanner/struct.Scanner.html
+ * [`ErrorMode::Strict`]: enum.ErrorMode.html#variant.Strict
+ * [`ErrorMode::Lossy`]: enum.ErrorMode.html#variant.Lossy
+ */
+#[derive(Debug)]
+pub struct TranscodingReader<R> {
+    inner: R,
+    encoding: Option<Encoding>,
+    fallback: Encoding,
+    mode: ErrorMode,
+    /// Input that hasn't been decoded yet.
+    raw: Vec<u8>,
+    /// The offset of the first byte of `raw` in the input.
+    offset: u64,
+    eof: bool,
+    out: Vec<u8>,
+    pos: usize,
+    error: Option<Error>,
+}
+
+impl<R: Read> TranscodingReader<R> {
+    fn with_encoding(inner: R, encoding: Option<Encoding>, fallback: Encoding) -> Self {
+        Self {
+            inner,
+            encoding,
+            fallback,
+            mode: ErrorMode::Strict,
+            raw: Vec::new(),
+            offset: 0,
+            eof: false,
+            out: Vec::new(),
+            pos: 0,
+            error: None,
+        }
+    }
+
+    /// Convert the text read from `inner` from the specified encoding. A byte
+    /// order mark is not removed.
+    pub fn new(inner: R, encoding: Encoding) -> Self {
+        Self::with_encoding(inner, Some(encoding), encoding)
+    }
+
+    /// Convert the text read from `inner` from the encoding given by its byte
+    /// order mark, which is removed, or from `fallback` if it doesn't have
+    /// one.
+    pub fn sniff(inner: R, fallback: Encoding) -> Self {
+        Self::with_encoding(inner, None, fallback)
+    }
+
+    /// Use the specified error mode.
+    pub fn error_mode(mut self, mode: ErrorMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The encoding being converted from, once it's known.
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.encoding
+    }
+
+    /// Get a reference to the underlying bit source.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Unwrap the reader, returning the underlying bit source. Any input
+    /// that's been read from it but not yet returned is lost.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Read another chunk of input into `raw`.
+    fn read_raw(&mut self) -> io::Result<()> {
+        let len = self.raw.len();
+        self.raw.resize(len + CHUNK, 0);
+        let result = self.inner.read(&mut self.raw[len..]);
+        let n = *result.as_ref().unwrap_or(&0);
+        self.raw.truncate(len + n);
+        self.eof = result? == 0;
+        Ok(())
+    }
+
+    /// Decode as much of `raw` as possible into `out`, stopping after the
+    /// first error in strict mode.
+    fn decode(&mut self, encoding: Encoding) {
+        let mut idx = 0;
+        while idx < self.raw.len() {
+            match decode_one(encoding, &self.raw[idx..], self.eof) {
+                Step::Char(c, len) => {
+                    let mut buf = [0; 4];
+                    self.out
+                        .extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                    idx += len;
+                }
+                Step::Invalid(len) => {
+                    let at = self.offset + idx as u64;
+                    idx += len;
+                    if self.mode == ErrorMode::Lossy {
+                        self.out.extend_from_slice("\u{FFFD}".as_bytes());
+                    } else {
+                        let msg = format!("Invalid {} input at byte {}", encoding, at);
+                        self.error = Some(Error::new(io::ErrorKind::InvalidData, msg));
+                        break;
+                    }
+                }
+                Step::Incomplete => break,
+            }
+        }
+        self.raw.drain(..idx);
+        self.offset += idx as u64;
+    }
+}
+
+impl<R: Read> Read for TranscodingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.fill_buf()?.read(buf)?;
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for TranscodingReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        while self.pos == self.out.len() {
+            if let Some(e) = self.error.take() {
+                return Err(e);
+            }
+            if self.eof && self.raw.is_empty() {
+                break;
+            }
+            if !self.eof {
+                self.read_raw()?;
+            }
+            let encoding = match self.encoding {
+                Some(encoding) => encoding,
+                None if self.raw.len() < 4 && !self.eof => continue,
+                None => {
+                    let (encoding, bom) =
+                        Encoding::from_bom(&self.raw).unwrap_or((self.fallback, 0));
+                    self.raw.drain(..bom);
+                    self.offset += bom as u64;
+                    self.encoding = Some(encoding);
+                    encoding
+                }
+            };
+            self.out.clear();
+            self.pos = 0;
+            self.decode(encoding);
+        }
+        Ok(&self.out[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.out.len());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::Trickle;
+
+    fn transcode(src: impl Read, encoding: Encoding) -> io::Result<String> {
+        let mut text = String::new();
+        TranscodingReader::new(src, encoding).read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    #[test]
+    fn converts_single_byte_encodings() -> io::Result<()> {
+        assert_eq!(
+            transcode(&b"caf\xe9 \x80"[..], Encoding::Latin1)?,
+            "café \u{80}"
+        );
+        assert_eq!(
+            transcode(&b"caf\xe9 \x80"[..], Encoding::Windows1252)?,
+            "café €"
+        );
+        assert_eq!(
+            transcode(&b"\xc9\xcd\xbb 1\xf8"[..], Encoding::Cp437)?,
+            "╔═╗ 1°"
+        );
+
+        let mut src = TranscodingReader::new(&b"ab\x81cd\n"[..], Encoding::Windows1252);
+        let mut line = String::new();
+        let err = src
+            .read_line(&mut line)
+            .expect_err("Decoded an undefined byte");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "Invalid Windows-1252 input at byte 2");
+        line.clear();
+        src.read_line(&mut line)?;
+        assert_eq!(line, "cd\n");
+        Ok(())
+    }
+
+    #[test]
+    fn sniffs_utf16_and_utf32() -> io::Result<()> {
+        // U+1F600 is a surrogate pair in UTF-16, split across every read.
+        let utf16 = b"\xfe\xff\x00h\xd8\x3d\xde\x00\x00!";
+        let mut text = String::new();
+        TranscodingReader::sniff(Trickle::new(utf16), Encoding::Latin1)
+            .read_to_string(&mut text)?;
+        assert_eq!(text, "h\u{1F600}!");
+
+        let utf32 = b"\xff\xfe\x00\x00h\x00\x00\x00\x00\xf6\x01\x00";
+        let mut src = TranscodingReader::sniff(Trickle::new(utf32), Encoding::Latin1);
+        text.clear();
+        src.read_to_string(&mut text)?;
+        assert_eq!(text, "h\u{1F600}");
+        assert_eq!(src.encoding(), Some(Encoding::Utf32Le));
+
+        let mut src = TranscodingReader::sniff(&b"ok"[..], Encoding::Cp437);
+        text.clear();
+        src.read_to_string(&mut text)?;
+        assert_eq!(
+            (text.as_str(), src.encoding()),
+            ("ok", Some(Encoding::Cp437))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn replaces_invalid_input_when_lossy() -> io::Result<()> {
+        let bad = b"a\x00\x00\xdc!\x00\x3d\xd8";
+        let mut text = String::new();
+        TranscodingReader::new(&bad[..], Encoding::Utf16Le)
+            .error_mode(ErrorMode::Lossy)
+            .read_to_string(&mut text)?;
+        assert_eq!(text, "a\u{FFFD}!\u{FFFD}");
+        assert!(transcode(&bad[..], Encoding::Utf16Le).is_err());
+        assert_eq!(
+            transcode(Trickle::new("né€".as_bytes()), Encoding::Utf8)?,
+            "né€"
+        );
+        assert!(transcode(&b"\xc0\xaf"[..], Encoding::Utf8).is_err());
+        Ok(())
+    }
+}